@@ -1,67 +1,216 @@
-fn main() {
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+fn main() -> io::Result<()> {
+    // inputs too large to embed are streamed from the path given on the command line,
+    // once for each part so only a single line is held in memory
+    if let Some(path) = std::env::args().nth(1) {
+        for (part, matcher) in [
+            ("part1", DigitMatcher::new([DIGITS])),
+            ("part2", DigitMatcher::new([DIGITS, ENGLISH])),
+        ] {
+            let sum = matcher.sum_reader(BufReader::new(File::open(&path)?), |_, _| ())?;
+            println!("{part}: {sum}");
+        }
+        return Ok(());
+    }
     dbg!(part1(include_str!("../input")));
     dbg!(part2(include_str!("../input")));
+    Ok(())
 }
 
-fn part1(input: &str) -> u32 {
-    let mut sum = 0;
-    for line in input.lines() {
-        let mut seen = None;
-        for ch in line.chars() {
-            if let Some(num) = ch.to_digit(10) {
-                if seen.is_none() {
-                    sum += num * 10;
-                }
-                seen = Some(num);
+type Vocabulary = &'static [(&'static str, u32)];
+
+const DIGITS: Vocabulary = &[
+    ("0", 0),
+    ("1", 1),
+    ("2", 2),
+    ("3", 3),
+    ("4", 4),
+    ("5", 5),
+    ("6", 6),
+    ("7", 7),
+    ("8", 8),
+    ("9", 9),
+];
+
+const ENGLISH: Vocabulary = &[
+    ("one", 1),
+    ("two", 2),
+    ("three", 3),
+    ("four", 4),
+    ("five", 5),
+    ("six", 6),
+    ("seven", 7),
+    ("eight", 8),
+    ("nine", 9),
+];
+
+#[allow(unused)]
+const GERMAN: Vocabulary = &[
+    ("null", 0),
+    ("eins", 1),
+    ("zwei", 2),
+    ("drei", 3),
+    ("vier", 4),
+    ("fünf", 5),
+    ("sechs", 6),
+    ("sieben", 7),
+    ("acht", 8),
+    ("neun", 9),
+];
+
+#[allow(unused)]
+const FRENCH: Vocabulary = &[
+    ("zéro", 0),
+    ("un", 1),
+    ("deux", 2),
+    ("trois", 3),
+    ("quatre", 4),
+    ("cinq", 5),
+    ("six", 6),
+    ("sept", 7),
+    ("huit", 8),
+    ("neuf", 9),
+];
+
+/// Aho-Corasick automaton over the bytes of all words of some vocabularies.
+/// Finds every (possibly overlapping) occurrence in a single pass.
+struct DigitMatcher {
+    nodes: Vec<Node>,
+}
+
+#[derive(Default)]
+struct Node {
+    next: HashMap<u8, usize>,
+    fail: usize,
+    /// (word length, value) of every word ending in this node, including via fail links
+    out: Vec<(usize, u32)>,
+}
+
+impl DigitMatcher {
+    fn new<'a>(vocabularies: impl IntoIterator<Item = &'a [(&'a str, u32)]>) -> Self {
+        let mut nodes = vec![Node::default()];
+        for &(word, value) in vocabularies.into_iter().flatten() {
+            let mut cur = 0;
+            for &b in word.as_bytes() {
+                cur = match nodes[cur].next.get(&b) {
+                    Some(&n) => n,
+                    None => {
+                        nodes.push(Node::default());
+                        let n = nodes.len() - 1;
+                        nodes[cur].next.insert(b, n);
+                        n
+                    }
+                };
             }
+            nodes[cur].out.push((word.len(), value));
         }
-        sum += seen.unwrap();
-    }
-    sum
-}
 
-fn part2(input: &str) -> u32 {
-    let mut sum = 0;
-
-    for mut line in input.lines() {
-        let mut seen = None;
-
-        while line.len() > 0 {
-            let num = if let Some(n) = line.chars().next().and_then(|n| n.to_digit(10)) {
-                Some(n)
-            } else {
-                let nums = [
-                    "one", "two", "three", "four", "five", "six", "seven", "eight", "nine",
-                ];
-                nums.iter()
-                    .enumerate()
-                    .filter_map(|(idx, name)| {
-                        if line.starts_with(name) {
-                            return Some(idx as u32 + 1);
-                        }
-                        None
-                    })
-                    .next()
-            };
+        // breadth first, so fail targets are complete before they are used
+        let mut queue: VecDeque<usize> = nodes[0].next.values().copied().collect();
+        while let Some(cur) = queue.pop_front() {
+            let edges: Vec<_> = nodes[cur].next.iter().map(|(&b, &n)| (b, n)).collect();
+            for (b, child) in edges {
+                let mut f = nodes[cur].fail;
+                let fail = loop {
+                    if let Some(&n) = nodes[f].next.get(&b) {
+                        break n;
+                    }
+                    if f == 0 {
+                        break 0;
+                    }
+                    f = nodes[f].fail;
+                };
+                nodes[child].fail = fail;
+                let inherited = nodes[fail].out.clone();
+                nodes[child].out.extend(inherited);
+                queue.push_back(child);
+            }
+        }
+        DigitMatcher { nodes }
+    }
 
-            if let Some(num) = num {
-                if seen.is_none() {
-                    sum += 10 * num;
+    /// All digits in `line`, ordered by the position where their word starts.
+    fn find_all(&self, line: &str) -> Vec<u32> {
+        let mut found = vec![];
+        let mut cur = 0;
+        for (idx, b) in line.bytes().enumerate() {
+            cur = loop {
+                if let Some(&n) = self.nodes[cur].next.get(&b) {
+                    break n;
+                }
+                if cur == 0 {
+                    break 0;
                 }
-                seen = Some(num);
+                cur = self.nodes[cur].fail;
+            };
+            for &(len, value) in &self.nodes[cur].out {
+                found.push((idx + 1 - len, value));
             }
-            (_, line) = line.split_at(1);
         }
-        sum += seen.unwrap();
+        found.sort_by_key(|&(start, _)| start);
+        found.into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// First and last digit combined, or `None` if the line has no digit at all.
+    fn calibration(&self, line: &str) -> Option<u32> {
+        let digits = self.find_all(line);
+        Some(digits.first()? * 10 + digits.last()?)
+    }
+
+    fn sum(&self, input: &str) -> u64 {
+        input
+            .lines()
+            .filter_map(|line| self.calibration(line))
+            .map(u64::from)
+            .sum()
+    }
+
+    /// Same as `sum`, but reads line by line so only one line is held in memory.
+    /// `on_line` gets every line number (starting at 0) with its calibration value.
+    fn sum_reader<R: BufRead>(
+        &self,
+        mut reader: R,
+        mut on_line: impl FnMut(usize, Option<u32>),
+    ) -> io::Result<u64> {
+        let mut sum = 0;
+        let mut buf = String::new();
+        let mut line_no = 0;
+        while reader.read_line(&mut buf)? > 0 {
+            let value = self.calibration(buf.trim_end_matches(['\n', '\r']));
+            on_line(line_no, value);
+            sum += u64::from(value.unwrap_or(0));
+            line_no += 1;
+            buf.clear();
+        }
+        Ok(sum)
     }
+}
+
+fn part1(input: &str) -> u64 {
+    DigitMatcher::new([DIGITS]).sum(input)
+}
 
-    sum
+fn part2(input: &str) -> u64 {
+    DigitMatcher::new([DIGITS, ENGLISH]).sum(input)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_part1() {
+        let input = "1abc2
+pqr3stu8vwx
+a1b2c3d4e5f
+treb7uchet
+";
+        assert_eq!(part1(input), 142);
+    }
+
     #[test]
     fn test_part2() {
         let input = "two1nine
@@ -74,4 +223,49 @@ zoneight234
 ";
         assert_eq!(part2(input), 281);
     }
+
+    #[test]
+    fn test_overlapping() {
+        let m = DigitMatcher::new([DIGITS, ENGLISH]);
+        assert_eq!(m.find_all("eightwo"), vec![8, 2]);
+        assert_eq!(m.find_all("xtwone3four"), vec![2, 1, 3, 4]);
+        assert_eq!(m.find_all("oneight"), vec![1, 8]);
+    }
+
+    #[test]
+    fn test_no_digits() {
+        let m = DigitMatcher::new([DIGITS, ENGLISH]);
+        assert_eq!(m.calibration("abcdef"), None);
+        assert_eq!(part2("abc\nfour\n"), 44);
+    }
+
+    #[test]
+    fn test_sum_reader() {
+        let input = "two1nine\r\neightwothree\nnothing\nzoneight234";
+        let m = DigitMatcher::new([DIGITS, ENGLISH]);
+        let mut values = vec![];
+        let sum = m
+            .sum_reader(input.as_bytes(), |line_no, value| {
+                values.push((line_no, value))
+            })
+            .unwrap();
+        assert_eq!(sum, m.sum(input));
+        assert_eq!(
+            values,
+            vec![(0, Some(29)), (1, Some(83)), (2, None), (3, Some(14))]
+        );
+    }
+
+    #[test]
+    fn test_other_vocabularies() {
+        let m = DigitMatcher::new([DIGITS, GERMAN, FRENCH]);
+        assert_eq!(m.find_all("fünfzwei7huit"), vec![5, 2, 7, 8]);
+        // "un" hides inside "neun"
+        assert_eq!(m.find_all("neuneins"), vec![9, 1, 1]);
+
+        let custom: &[(&str, u32)] = &[("zero", 0), ("nil", 0)];
+        let m = DigitMatcher::new([DIGITS, ENGLISH, custom]);
+        assert_eq!(m.calibration("zerotwo"), Some(2));
+        assert_eq!(m.calibration("sevennil"), Some(70));
+    }
 }
@@ -16,34 +16,67 @@ struct Schm {
     syms: HashMap<Pt, char>,
 }
 
+struct ParseOpts {
+    /// Which bytes (other than digits, '.' and line breaks) are symbols.
+    is_symbol: fn(u8) -> bool,
+    /// Whether a '-' right in front of a digit makes the number negative.
+    signed: bool,
+}
+
+impl Default for ParseOpts {
+    fn default() -> Self {
+        ParseOpts {
+            is_symbol: |b| !b.is_ascii_whitespace(),
+            signed: false,
+        }
+    }
+}
+
 fn parse(input: &str) -> Schm {
+    parse_with(input, &ParseOpts::default())
+}
+
+fn parse_with(input: &str, opts: &ParseOpts) -> Schm {
     let mut nums = vec![];
     let mut syms = HashMap::new();
 
+    // start, magnitude so far, negative
+    let mut num: Option<(Pt, i32, bool)> = None;
+    let flush = |num: &mut Option<(Pt, i32, bool)>, nums: &mut Vec<(Pt, i32)>| {
+        if let Some((pt, n, neg)) = num.take() {
+            nums.push((pt, if neg { -n } else { n }));
+        }
+    };
+
+    let bytes = input.as_bytes();
     let mut pt = Pt(0, 0);
-    let mut num = None;
-    for b in input.bytes() {
+    for (idx, &b) in bytes.iter().enumerate() {
         if b.is_ascii_digit() {
+            let d = (b - b'0') as i32;
             num = num
-                .map(|(pt, n)| (pt, 10 * n + (b - b'0') as i32))
-                .or(Some((pt, (b - b'0') as i32)));
+                .map(|(pt, n, neg)| (pt, 10 * n + d, neg))
+                .or(Some((pt, d, false)));
         } else {
-            if let Some(num) = num.take() {
-                nums.push(num);
-            }
+            flush(&mut num, &mut nums);
             match b {
-                b'.' => (),
+                b'-' if opts.signed && bytes.get(idx + 1).is_some_and(u8::is_ascii_digit) => {
+                    num = Some((pt, 0, true));
+                }
+                b'.' | b'\r' => (),
                 b'\n' => {
                     pt.1 += 1;
                     pt.0 = -1;
                 }
-                _ => {
+                _ if (opts.is_symbol)(b) => {
                     syms.insert(pt, b as char);
                 }
+                _ => (),
             }
         }
         pt.0 += 1;
     }
+    // the last number may touch the end of the input
+    flush(&mut num, &mut nums);
 
     Schm { nums, syms }
 }
@@ -54,7 +87,11 @@ fn main() {
     dbg!(part2(input));
 }
 
+/// Cells covered by `n`, including its sign.
 fn dcnt(mut n: i32) -> i32 {
+    if n < 0 {
+        return 1 + dcnt(-n);
+    }
     if n == 0 {
         return 1;
     }
@@ -66,54 +103,102 @@ fn dcnt(mut n: i32) -> i32 {
     cnt
 }
 
-fn bbox(pt: Pt, n: i32) -> (std::ops::Range<i32>, std::ops::Range<i32>) {
-    ((pt.0 - 1)..(pt.0 + dcnt(n) + 1), (pt.1 - 1)..(pt.1 + 2))
+/// Bipartite adjacency between the numbers and symbols of a schematic.
+/// Numbers are referred to by their index into `Schm::nums`.
+struct PartGraph<'a> {
+    schm: &'a Schm,
+    sym_nums: HashMap<Pt, Vec<usize>>,
+    num_syms: Vec<Vec<Pt>>,
 }
 
-fn part1(input: &str) -> i32 {
-    let mut sum = 0;
-    let schm = parse(input);
-    'num: for (pt, n) in &schm.nums {
-        let bbox = bbox(*pt, *n);
-        for y in bbox.1.clone() {
-            for x in bbox.0.clone() {
-                if schm.syms.contains_key(&Pt(x, y)) {
-                    // dbg!((n, pt, x, y));
-                    sum += n;
-                    continue 'num;
-                }
+impl<'a> PartGraph<'a> {
+    fn new(schm: &'a Schm) -> Self {
+        // every cell covered by a digit, pointing to its number
+        let mut cells = HashMap::new();
+        for (idx, (pt, n)) in schm.nums.iter().enumerate() {
+            for x in pt.0..pt.0 + dcnt(*n) {
+                cells.insert(Pt(x, pt.1), idx);
             }
         }
+
+        let mut sym_nums = HashMap::new();
+        let mut num_syms = vec![vec![]; schm.nums.len()];
+        for &sym in schm.syms.keys() {
+            let mut adjacent: Vec<usize> = (-1..=1)
+                .flat_map(|dy| (-1..=1).map(move |dx| Pt(dx, dy)))
+                .filter_map(|d| cells.get(&(sym + d)).copied())
+                .collect();
+            adjacent.sort();
+            adjacent.dedup();
+            for &idx in &adjacent {
+                num_syms[idx].push(sym);
+            }
+            sym_nums.insert(sym, adjacent);
+        }
+
+        PartGraph {
+            schm,
+            sym_nums,
+            num_syms,
+        }
     }
-    sum
-}
 
-fn part2(input: &str) -> i32 {
-    let schm = parse(input);
-    let mut result = 0;
-    for star in schm.syms.iter().filter(|e| *e.1 == '*') {
-        let (prd, cnt) = schm
+    /// Values of the numbers touching the symbol at `sym`.
+    fn nums_at(&self, sym: Pt) -> impl Iterator<Item = i32> + '_ {
+        self.sym_nums
+            .get(&sym)
+            .into_iter()
+            .flatten()
+            .map(|&idx| self.schm.nums[idx].1)
+    }
+
+    /// Positions of all symbols touching exactly `k` numbers.
+    fn syms_with_degree(&self, k: usize) -> impl Iterator<Item = Pt> + '_ {
+        self.sym_nums
+            .iter()
+            .filter(move |(_, nums)| nums.len() == k)
+            .map(|(pt, _)| *pt)
+    }
+
+    /// Sum of the products of the numbers around every `sym` touching exactly `arity` numbers.
+    fn gear_ratio_sum(&self, sym: char, arity: usize) -> i32 {
+        self.syms_with_degree(arity)
+            .filter(|pt| self.schm.syms[pt] == sym)
+            .map(|pt| self.nums_at(pt).product::<i32>())
+            .sum()
+    }
+
+    /// Numbers not touching any symbol.
+    fn orphans(&self) -> impl Iterator<Item = &(Pt, i32)> + '_ {
+        self.schm
             .nums
             .iter()
-            .filter(|num| {
-                let bbox = bbox(num.0, num.1);
-                bbox.0.contains(&star.0 .0) && bbox.1.contains(&star.0 .1)
-            })
-            .fold((1, 0), |(prd, cnt), ele| (prd * ele.1, cnt + 1));
-        if cnt == 2 {
-            result += prd;
-        }
+            .zip(&self.num_syms)
+            .filter(|(_, syms)| syms.is_empty())
+            .map(|(num, _)| num)
     }
-    result
+}
+
+fn part1(input: &str) -> i32 {
+    let schm = parse(input);
+    let total: i32 = schm.nums.iter().map(|num| num.1).sum();
+    total
+        - PartGraph::new(&schm)
+            .orphans()
+            .map(|num| num.1)
+            .sum::<i32>()
+}
+
+fn part2(input: &str) -> i32 {
+    let schm = parse(input);
+    PartGraph::new(&schm).gear_ratio_sum('*', 2)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_part1() {
-        let input = "467..114..
+    const EXAMPLE: &str = "467..114..
 ...*......
 ..35..633.
 ......#...
@@ -123,7 +208,91 @@ mod tests {
 ......755.
 ...$.*....
 .664.598..";
-        assert_eq!(4361, part1(input));
-        assert_eq!(467835, part2(input));
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(4361, part1(EXAMPLE));
+        assert_eq!(467835, part2(EXAMPLE));
+    }
+
+    #[test]
+    fn test_graph() {
+        let schm = parse(EXAMPLE);
+        let graph = PartGraph::new(&schm);
+        let mut orphans: Vec<_> = graph.orphans().map(|num| num.1).collect();
+        orphans.sort();
+        assert_eq!(orphans, vec![58, 114]);
+
+        let mut around: Vec<_> = graph.nums_at(Pt(3, 1)).collect();
+        around.sort();
+        assert_eq!(around, vec![35, 467]);
+        assert_eq!(graph.nums_at(Pt(0, 0)).count(), 0);
+
+        let mut single: Vec<_> = graph.syms_with_degree(1).collect();
+        single.sort_by_key(|pt| (pt.1, pt.0));
+        assert_eq!(single, vec![Pt(6, 3), Pt(3, 4), Pt(5, 5), Pt(3, 8)]);
+        assert_eq!(graph.gear_ratio_sum('*', 1), 617);
+        assert_eq!(graph.gear_ratio_sum('$', 1), 664);
+        assert_eq!(graph.gear_ratio_sum('#', 2), 0);
+    }
+
+    #[test]
+    fn test_parse_crlf() {
+        let schm = parse(&EXAMPLE.replace('\n', "\r\n"));
+        assert_eq!(schm.syms.len(), parse(EXAMPLE).syms.len());
+        assert!(!schm.syms.values().any(|&c| c == '\r'));
+        assert_eq!(part1(&EXAMPLE.replace('\n', "\r\n")), 4361);
+    }
+
+    #[test]
+    fn test_parse_trailing_number() {
+        let schm = parse("..*\n.12");
+        assert_eq!(schm.nums, vec![(Pt(1, 1), 12)]);
+        assert_eq!(part1("..*\n.12"), 12);
+        assert_eq!(part1("..*\n.12\n"), 12);
+    }
+
+    #[test]
+    fn test_parse_symbol_class() {
+        let opts = ParseOpts {
+            is_symbol: |b| b == b'*',
+            ..Default::default()
+        };
+        let schm = parse_with("1#.\n..*\n5..", &opts);
+        assert_eq!(
+            schm.syms.into_iter().collect::<Vec<_>>(),
+            vec![(Pt(2, 1), '*')]
+        );
+    }
+
+    #[test]
+    fn test_parse_signed() {
+        let input = "-12*3\n.....\n4-5..";
+        let unsigned = parse(input);
+        assert_eq!(
+            unsigned.nums,
+            vec![(Pt(1, 0), 12), (Pt(4, 0), 3), (Pt(0, 2), 4), (Pt(2, 2), 5)]
+        );
+        assert_eq!(unsigned.syms.len(), 3);
+
+        let opts = ParseOpts {
+            signed: true,
+            ..Default::default()
+        };
+        let signed = parse_with(input, &opts);
+        assert_eq!(
+            signed.nums,
+            vec![
+                (Pt(0, 0), -12),
+                (Pt(4, 0), 3),
+                (Pt(0, 2), 4),
+                (Pt(1, 2), -5)
+            ]
+        );
+        assert_eq!(
+            signed.syms.into_iter().collect::<Vec<_>>(),
+            vec![(Pt(3, 0), '*')]
+        );
+        assert_eq!(dcnt(-12), 3);
     }
 }
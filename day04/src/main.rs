@@ -1,7 +1,7 @@
-use std::collections::HashMap;
+use std::collections::HashSet;
 
 struct Card {
-    winners: Vec<i32>,
+    winners: HashSet<i32>,
     have: Vec<i32>,
 }
 
@@ -13,12 +13,10 @@ fn parse(input: &str) -> Vec<Card> {
             let (winners, have) = rest.trim().split_once(" | ").unwrap();
             Card {
                 winners: winners
-                    .trim()
                     .split_whitespace()
                     .map(|n| n.parse::<i32>().unwrap())
                     .collect(),
                 have: have
-                    .trim()
                     .split_whitespace()
                     .map(|n| n.parse::<i32>().unwrap())
                     .collect(),
@@ -35,35 +33,46 @@ impl Card {
             .count()
     }
 
-    fn score(&self) -> i32 {
+    /// `None` if the score does not fit into a `u64`.
+    fn score(&self) -> Option<u64> {
         match self.winner_cnt() {
-            0 => 0,
-            n => 1 << (n - 1),
+            0 => Some(0),
+            n => 1u64.checked_shl(u32::try_from(n - 1).ok()?),
         }
     }
 }
 
-fn part1(input: &str) -> i32 {
+fn part1(input: &str) -> Option<u64> {
     let cards = parse(input);
-    cards.iter().map(Card::score).sum()
+    cards
+        .iter()
+        .try_fold(0u64, |sum, card| sum.checked_add(card.score()?))
 }
 
-fn part2(input: &str) -> i32 {
-    let cards = parse(input);
-    let mut memo = HashMap::new();
-    fn card_cnt(idx: usize, cards: &[Card], memo: &mut HashMap<usize, i32>) -> i32 {
-        if let Some(n) = memo.get(&idx) {
-            *n
-        } else if let Some(card) = cards.get(idx) {
-            let winnings = idx + 1..idx + 1 + card.winner_cnt();
-            let result = 1 + winnings.map(|idx| card_cnt(idx, cards, memo)).sum::<i32>();
-            memo.insert(idx, result);
-            result
-        } else {
-            0
-        }
+/// Number of copies held of every card after all winnings are cashed in,
+/// or `None` if a count does not fit into a `u64`.
+fn copy_counts(cards: &[Card]) -> Option<Vec<u64>> {
+    // copies won from earlier cards which still cover this one, and how many of
+    // them stop covering at each card
+    let mut carry = 0u64;
+    let mut expired = vec![0u64; cards.len() + 1];
+    let mut counts = Vec::with_capacity(cards.len());
+    for (idx, card) in cards.iter().enumerate() {
+        carry = carry.checked_sub(expired[idx])?;
+        let count = carry.checked_add(1)?;
+        let end = (idx + 1 + card.winner_cnt()).min(cards.len());
+        carry = carry.checked_add(count)?;
+        expired[end] = expired[end].checked_add(count)?;
+        counts.push(count);
     }
-    (0..cards.len()).map(|n| card_cnt(n, &cards, &mut memo)).sum()
+    Some(counts)
+}
+
+fn part2(input: &str) -> Option<u64> {
+    let cards = parse(input);
+    copy_counts(&cards)?
+        .into_iter()
+        .try_fold(0u64, |sum, n| sum.checked_add(n))
 }
 
 fn main() {
@@ -76,15 +85,45 @@ fn main() {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_part2() {
-        let input = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
+    const EXAMPLE: &str = "Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
 Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
 Card 3:  1 21 53 59 44 | 69 82 63 72 16 21 14  1
 Card 4: 41 92 73 84 69 | 59 84 76 51 58  5 54 83
 Card 5: 87 83 26 28 32 | 88 30 70 12 93 22 82 36
 Card 6: 31 18 13 56 72 | 74 77 10 23 35 67 36 11
 ";
-        assert_eq!(30, part2(input));
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(Some(13), part1(EXAMPLE));
+    }
+
+    #[test]
+    fn test_score_overflow() {
+        let card = |winners: usize| Card {
+            winners: HashSet::from([1]),
+            have: vec![1; winners],
+        };
+        assert_eq!(card(64).score(), Some(1 << 63));
+        assert_eq!(card(65).score(), None);
+        assert_eq!(card(300).score(), None);
+    }
+
+    #[test]
+    fn test_part2() {
+        assert_eq!(Some(30), part2(EXAMPLE));
+        assert_eq!(Some(vec![1, 2, 4, 8, 14, 1]), copy_counts(&parse(EXAMPLE)));
+    }
+
+    #[test]
+    fn test_copy_counts_overflow() {
+        // every card wins a copy of each following card, doubling the count each time
+        let card = || Card {
+            winners: HashSet::from([1]),
+            have: vec![1; 100],
+        };
+        let cards: Vec<_> = (0..100).map(|_| card()).collect();
+        assert_eq!(copy_counts(&cards[..64]).unwrap()[63], 1 << 63);
+        assert_eq!(copy_counts(&cards), None);
     }
 }
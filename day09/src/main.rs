@@ -1,7 +1,16 @@
-fn main() {
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+
+fn main() -> io::Result<()> {
+    // inputs too large to embed are streamed from the path given on the command line
+    if let Some(path) = std::env::args().nth(1) {
+        dbg!(stream(BufReader::new(File::open(path)?), |_, _, _| ())?);
+        return Ok(());
+    }
     let input = include_str!("../input");
     dbg!(part1(input));
     dbg!(part2(input));
+    Ok(())
 }
 
 fn part1(input: &str) -> i64 {
@@ -12,8 +21,35 @@ fn part2(input: &str) -> i64 {
     input.lines().map(extrap_back).sum()
 }
 
+/// Computes the sums of part1 and part2 in one pass, reading one line at a time.
+/// `on_line` gets every line number (starting at 0) with its forward and backward extrapolation.
+fn stream<R: BufRead>(
+    mut reader: R,
+    mut on_line: impl FnMut(usize, i64, i64),
+) -> io::Result<(i64, i64)> {
+    let (mut fwd_sum, mut back_sum) = (0, 0);
+    let mut buf = String::new();
+    let mut line_no = 0;
+    while reader.read_line(&mut buf)? > 0 {
+        let line = buf.trim();
+        if !line.is_empty() {
+            let levels = levels(line);
+            let (fwd, back) = (extrap_levels(&levels), extrap_back_levels(&levels));
+            on_line(line_no, fwd, back);
+            fwd_sum += fwd;
+            back_sum += back;
+        }
+        line_no += 1;
+        buf.clear();
+    }
+    Ok((fwd_sum, back_sum))
+}
+
 fn extrap(line: &str) -> i64 {
-    let levels = levels(line);
+    extrap_levels(&levels(line))
+}
+
+fn extrap_levels(levels: &[Vec<i64>]) -> i64 {
     let mut result = 0;
     for n in levels.iter().rev() {
         result += n.last().unwrap();
@@ -22,7 +58,10 @@ fn extrap(line: &str) -> i64 {
 }
 
 fn extrap_back(line: &str) -> i64 {
-    let levels = levels(line);
+    extrap_back_levels(&levels(line))
+}
+
+fn extrap_back_levels(levels: &[Vec<i64>]) -> i64 {
     let mut result = 0;
     for n in levels.iter().rev() {
         result = n[0] - result;
@@ -50,3 +89,30 @@ fn levels(line: &str) -> Vec<Vec<i64>> {
     }
     levels
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "0 3 6 9 12 15
+1 3 6 10 15 21
+10 13 16 21 30 45
+";
+
+    #[test]
+    fn test_parts() {
+        assert_eq!(part1(EXAMPLE), 114);
+        assert_eq!(part2(EXAMPLE), 2);
+    }
+
+    #[test]
+    fn test_stream() {
+        let mut per_line = vec![];
+        let sums = stream(EXAMPLE.as_bytes(), |line_no, fwd, back| {
+            per_line.push((line_no, fwd, back))
+        })
+        .unwrap();
+        assert_eq!(sums, (114, 2));
+        assert_eq!(per_line, vec![(0, 18, -3), (1, 28, 0), (2, 68, 5)]);
+    }
+}
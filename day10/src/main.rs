@@ -1,121 +1,328 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::HashSet;
 
 fn main() {
     let input = include_str!("../input");
-    dbg!(part1_and_2(input));
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--render") {
+        let style = if args.iter().any(|arg| arg == "--plain") {
+            Style::Plain
+        } else {
+            Style::Ansi
+        };
+        let (map, start_pos, path) = resolve(input).unwrap();
+        print!("{}", render(&map, start_pos, &path, style));
+    }
+    if args.iter().any(|arg| arg == "--loops") {
+        let (map, _, _) = resolve(input).unwrap();
+        for pipe_loop in find_loops(&map) {
+            println!(
+                "loop at {:?}: {} tiles, {} enclosed apart from nested loops",
+                pipe_loop.path[0],
+                pipe_loop.path.len(),
+                pipe_loop.enclosed
+            );
+        }
+    }
+    dbg!(part1_and_2(input).unwrap());
 }
 
-fn part1_and_2(input: &str) -> (usize,usize) {
-    let map = parse(input);
+#[derive(Debug, PartialEq, Eq)]
+enum MapError {
+    NoStart,
+    MultipleStarts,
+    /// No shape of the start tile closes a loop.
+    NoLoop,
+    /// Several shapes of the start tile close a loop.
+    AmbiguousStart(Vec<u8>),
+}
 
-    // find S
-    let start_pos = (0..map.0.len())
-        .filter_map(|y| {
-            (0..map.0[y].len())
-                .filter_map(|x| {
-                    if map.0[y][x] == NORTH | EAST | WEST | SOUTH {
-                        Some([x as i64, y as i64])
-                    } else {
-                        None
-                    }
-                })
-                .next()
+fn part1_and_2(input: &str) -> Result<(usize, usize), MapError> {
+    let (_, _, path) = resolve(input)?;
+    Ok((path.len() / 2, enclosed_cnt(&path)))
+}
+
+/// Map with the start square replaced by its correct tile, start position and loop.
+type Resolved = (Map, [i64; 2], Vec<[i64; 2]>);
+
+/// Parses the map, replaces the start square by its correct tile and walks the loop.
+fn resolve(input: &str) -> Result<Resolved, MapError> {
+    let mut map = parse(input);
+    let start_pos = find_start(&map)?;
+    let start_tile = resolve_start_tile(&map, start_pos)?;
+    map.0[start_pos[1] as usize][start_pos[0] as usize] = start_tile;
+    let path = extract_loop(&map, start_pos).unwrap();
+    Ok((map, start_pos, path))
+}
+
+fn find_start(map: &Map) -> Result<[i64; 2], MapError> {
+    let mut starts = (0..map.0.len()).flat_map(|y| {
+        (0..map.0[y].len())
+            .filter(move |&x| map.0[y][x] == NORTH | EAST | WEST | SOUTH)
+            .map(move |x| [x as i64, y as i64])
+    });
+    match (starts.next(), starts.next()) {
+        (Some(start_pos), None) => Ok(start_pos),
+        (None, _) => Err(MapError::NoStart),
+        (Some(_), Some(_)) => Err(MapError::MultipleStarts),
+    }
+}
+
+const SHAPES: [u8; 6] = [
+    NORTH | SOUTH,
+    EAST | WEST,
+    NORTH | EAST,
+    NORTH | WEST,
+    SOUTH | WEST,
+    SOUTH | EAST,
+];
+
+/// Finds the one tile shape for the start square which closes a loop through it.
+/// Pipes merely pointing at the start square don't matter.
+fn resolve_start_tile(map: &Map, start_pos: [i64; 2]) -> Result<u8, MapError> {
+    let (x, y) = (start_pos[0] as usize, start_pos[1] as usize);
+    let mut trial = map.clone();
+    let closing: Vec<u8> = SHAPES
+        .into_iter()
+        .filter(|&shape| {
+            trial.0[y][x] = shape;
+            extract_loop(&trial, start_pos).is_some()
         })
-        .next()
-        .unwrap();
-
-    let mut dists = HashMap::new();
-    
-    let mut work = VecDeque::new();
-    work.push_back((0usize,start_pos));
-    while let Some((cost, pos)) = work.pop_front() {
-        if let Some(prev_cost) = dists.get(&pos) {
-            if *prev_cost <= cost {
-                continue; // been here cheaper before
-            }
+        .collect();
+    match closing[..] {
+        [shape] => Ok(shape),
+        [] => Err(MapError::NoLoop),
+        _ => Err(MapError::AmbiguousStart(closing)),
+    }
+}
+
+fn step(pos: [i64; 2], dir: u8) -> [i64; 2] {
+    match dir {
+        NORTH => [pos[0], pos[1] - 1],
+        EAST => [pos[0] + 1, pos[1]],
+        SOUTH => [pos[0], pos[1] + 1],
+        WEST => [pos[0] - 1, pos[1]],
+        _ => unreachable!(),
+    }
+}
+
+fn opposite(dir: u8) -> u8 {
+    ((dir << 2) | (dir >> 2)) & (NORTH | EAST | SOUTH | WEST)
+}
+
+/// Walks the loop through `start`, which must already hold its real tile.
+/// Returns every tile of the loop in walking order, or `None` if the pipes don't close.
+fn extract_loop(map: &Map, start: [i64; 2]) -> Option<Vec<[i64; 2]>> {
+    match walk(map, start) {
+        (path, true) => Some(path),
+        (_, false) => None,
+    }
+}
+
+/// Follows the pipes from `start` until they lead back to it or break off.
+/// Returns the tiles visited and whether they closed a loop.
+fn walk(map: &Map, start: [i64; 2]) -> (Vec<[i64; 2]>, bool) {
+    let mut path = vec![];
+    let Some(start_tile) = map.get(start) else {
+        return (path, false);
+    };
+    // leave through the lowest direction of the start tile
+    let mut dir = start_tile & start_tile.wrapping_neg();
+    let mut pos = start;
+    loop {
+        path.push(pos);
+        if dir == 0 {
+            return (path, false);
         }
-        dists.insert(pos, cost);
-
-        let checks = [
-            ([pos[0]+0, pos[1]-1], NORTH, SOUTH),
-            ([pos[0]+1, pos[1]+0], EAST, WEST),
-            ([pos[0]+0, pos[1]+1], SOUTH, NORTH),
-            ([pos[0]-1, pos[1]+0], WEST, EAST),
-        ];
-        let here = map.get(pos).unwrap();
-
-        for (there_pos, here_req, there_req) in checks {
-            if here & here_req == 0 {
-                continue;
-            }
-            if let Some(tile) = map.get(there_pos) {
-                if tile & there_req != 0 {
-                    work.push_back((cost + 1, there_pos));
+        pos = step(pos, dir);
+        let entry = opposite(dir);
+        match map.get(pos) {
+            Some(tile) if tile & entry != 0 => {
+                if pos == start {
+                    return (path, true);
+                }
+                dir = tile & !entry;
+                if dir.count_ones() != 1 {
+                    return (path, false);
                 }
             }
+            _ => return (path, false),
         }
     }
+}
+
+struct PipeLoop {
+    /// Tiles of the loop in walking order.
+    path: Vec<[i64; 2]>,
+    /// Tiles enclosed by the loop that are not part of loops nested inside it.
+    enclosed: usize,
+}
 
-    let (_,max_dist) = dists.iter().max_by_key(|(_k,v)| **v).unwrap();
-
-    // for part 2, we need to have the start square replaced by its correct tile
-    let mut start_tile = 0;
-    let checks = [
-        ([start_pos[0]+0, start_pos[1]-1], NORTH, SOUTH),
-        ([start_pos[0]+1, start_pos[1]+0], EAST, WEST),
-        ([start_pos[0]+0, start_pos[1]+1], SOUTH, NORTH),
-        ([start_pos[0]-1, start_pos[1]+0], WEST, EAST),
-    ];
-    for (there, here_req, there_req) in checks {
-        if let Some(tile) = map.get(there) {
-            if tile & there_req != 0 {
-                start_tile |= here_req;
+/// Every separate loop of pipes in the map, in order of their top-left tile.
+/// `map` must have the start square replaced by its correct tile.
+fn find_loops(map: &Map) -> Vec<PipeLoop> {
+    let mut seen = HashSet::new();
+    let mut loops = vec![];
+    for y in 0..map.0.len() {
+        for x in 0..map.0[y].len() {
+            let pos = [x as i64, y as i64];
+            if map.0[y][x].count_ones() != 2 || seen.contains(&pos) {
+                continue;
+            }
+            // a walk is reversible, so none of its tiles is on a loop unless it closes
+            let (path, closed) = walk(map, pos);
+            seen.extend(path.iter().copied());
+            if closed {
+                let enclosed = enclosed_cnt(&path);
+                loops.push(PipeLoop { path, enclosed });
             }
         }
     }
-    let mut map = map;
-    map.0[start_pos[1] as usize][start_pos[0] as usize] = start_tile;
+    // loops don't cross, so one tile tells whether a whole loop is nested
+    let nested: Vec<usize> = (0..loops.len())
+        .map(|outer| {
+            (0..loops.len())
+                .filter(|&inner| inner != outer)
+                .filter(|&inner| inside(&loops[outer].path, loops[inner].path[0]))
+                .map(|inner| loops[inner].path.len())
+                .sum()
+        })
+        .collect();
+    for (pipe_loop, nested) in loops.iter_mut().zip(nested) {
+        pipe_loop.enclosed -= nested;
+    }
+    loops
+}
 
-    let mut inside_cnt = 0;
-    
-    for y in 0..map.0.len() {
-        let mut state = 0;
-        for x in 0..map.0[0].len() {
-            // cast a ray across a row, and see how many spaces are inside
+/// Whether `pos`, which must not be on the loop, is enclosed by it.
+/// Counts the vertical steps of the loop crossing a ray east of `pos`,
+/// shifted half a tile south so it never runs along the loop.
+fn inside(path: &[[i64; 2]], pos: [i64; 2]) -> bool {
+    path.iter()
+        .zip(path.iter().cycle().skip(1))
+        .filter(|(a, b)| a[0] == b[0] && a[0] > pos[0] && a[1].min(b[1]) == pos[1])
+        .count()
+        % 2
+        == 1
+}
+
+/// Number of tiles enclosed by a loop, by Pick's theorem: A = i + b/2 - 1,
+/// with the area A from the shoelace formula and b the number of loop tiles.
+fn enclosed_cnt(path: &[[i64; 2]]) -> usize {
+    let twice_area: i64 = path
+        .iter()
+        .zip(path.iter().cycle().skip(1))
+        .map(|(a, b)| a[0] * b[1] - b[0] * a[1])
+        .sum();
+    ((twice_area.abs() - path.len() as i64) / 2 + 1) as usize
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Region {
+    Loop,
+    Inside,
+    Outside,
+}
+
+/// Sorts every tile into loop, inside or outside by casting a ray across each row.
+/// `map` must have the start square replaced by its correct tile.
+fn classify(map: &Map, path: &[[i64; 2]]) -> Vec<Vec<Region>> {
+    let on_loop: HashSet<_> = path.iter().copied().collect();
+    (0..map.0.len())
+        .map(|y| {
+            let mut state = 0;
+            (0..map.0[y].len())
+                .map(|x| {
+                    let pos = [x as i64, y as i64];
+                    if on_loop.contains(&pos) {
+                        state ^= map.get(pos).unwrap() & (NORTH | SOUTH);
+                        Region::Loop
+                    } else if state == NORTH | SOUTH {
+                        Region::Inside
+                    } else {
+                        Region::Outside
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn enclosed_cnt_raycast(map: &Map, path: &[[i64; 2]]) -> usize {
+    classify(map, path)
+        .iter()
+        .flatten()
+        .filter(|region| **region == Region::Inside)
+        .count()
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Style {
+    /// Loop drawn with box-drawing characters, the start tile with heavy lines.
+    Plain,
+    /// Like `Plain`, with loop, start, inside and outside in different colours.
+    Ansi,
+}
+
+fn glyph(tile: u8, heavy: bool) -> char {
+    let (light, heavy_ch) = match tile {
+        t if t == NORTH | SOUTH => ('│', '┃'),
+        t if t == EAST | WEST => ('─', '━'),
+        t if t == NORTH | EAST => ('└', '┗'),
+        t if t == NORTH | WEST => ('┘', '┛'),
+        t if t == SOUTH | WEST => ('┐', '┓'),
+        t if t == SOUTH | EAST => ('┌', '┏'),
+        _ => ('?', '?'),
+    };
+    if heavy {
+        heavy_ch
+    } else {
+        light
+    }
+}
+
+/// Draws the loop, with every other tile marked as `I`nside or `O`utside.
+/// `map` must have the start square replaced by its correct tile.
+fn render(map: &Map, start_pos: [i64; 2], path: &[[i64; 2]], style: Style) -> String {
+    let mut out = String::new();
+    for (y, row) in classify(map, path).iter().enumerate() {
+        for (x, region) in row.iter().enumerate() {
             let pos = [x as i64, y as i64];
-            let is_edge = dists.contains_key(&pos);
-            let tile = map.get(pos).unwrap();
-            if is_edge {
-                state ^= tile & (NORTH|SOUTH);
-                // match state {
-                //     NORTH => print!("N"),
-                //     SOUTH => print!("S"),
-                //     n if n == NORTH|SOUTH => print!("*"),
-                //     0 => print!("0"),
-                //     _=> unimplemented!()
-                // }
-            } else if state == NORTH|SOUTH {
-                inside_cnt += 1;
-                // print!("I");
+            let is_start = pos == start_pos;
+            let ch = match region {
+                Region::Loop => glyph(map.get(pos).unwrap(), is_start),
+                Region::Inside => 'I',
+                Region::Outside => 'O',
+            };
+            if style == Style::Ansi {
+                let color = match region {
+                    Region::Loop if is_start => "\x1b[1;33m",
+                    Region::Loop => "\x1b[1;32m",
+                    Region::Inside => "\x1b[31m",
+                    Region::Outside => "\x1b[2m",
+                };
+                out.push_str(color);
+                out.push(ch);
+                out.push_str("\x1b[0m");
             } else {
-                // print!("O");
+                out.push(ch);
             }
         }
-        println!();
+        out.push('\n');
     }
-
-    (*max_dist,inside_cnt)
+    out
 }
 
+#[derive(Clone)]
 struct Map(Vec<Vec<u8>>);
 
 impl Map {
-    fn get(&self, pos: [i64;2]) -> Option<u8> {
+    fn get(&self, pos: [i64; 2]) -> Option<u8> {
         if pos[1] >= 0 {
             if let Some(row) = self.0.get(pos[1] as usize) {
                 if pos[0] >= 0 {
                     return row.get(pos[0] as usize).copied();
-                } 
+                }
             }
         }
         None
@@ -131,7 +338,8 @@ fn parse(input: &str) -> Map {
     Map(input
         .lines()
         .map(|line| {
-            line.trim().chars()
+            line.trim()
+                .chars()
                 .map(|ch| match ch {
                     '|' => NORTH | SOUTH,
                     '-' => EAST | WEST,
@@ -141,22 +349,20 @@ fn parse(input: &str) -> Map {
                     'F' => SOUTH | EAST,
                     '.' => 0,
                     'S' => NORTH | EAST | WEST | SOUTH,
-                    _ => unimplemented!()
+                    _ => unimplemented!(),
                 })
                 .collect()
         })
         .collect())
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     #[test]
     fn test_complex() {
-        let input = 
-        "FF7FSF7F7F7F7F7F---7
+        let input = "FF7FSF7F7F7F7F7F---7
          L|LJ||||||||||||F--J
          FL-7LJLJ||||||LJL-77
          F--JF--7||LJLJ7F7FJ-
@@ -166,7 +372,7 @@ mod tests {
          7-L-JL7||F7|L7F-7F7|
          L.L7LFJ|||||FJL7||LJ
          L7JLJL-JLJLJL--JLJ.L";
-        assert_eq!(part1_and_2(input).1, 10);
+        assert_eq!(part1_and_2(input).unwrap().1, 10);
     }
 
     #[test]
@@ -180,6 +386,140 @@ mod tests {
         .|..|.|..|.
         .L--J.L--J.
         ...........";
-        assert_eq!(part1_and_2(input).1, 4);
+        assert_eq!(part1_and_2(input).unwrap().1, 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_part1() {
+        let input = "..F7.
+            .FJ|.
+            SJ.L7
+            |F--J
+            LJ...";
+        assert_eq!(part1_and_2(input).unwrap().0, 8);
+    }
+
+    fn check_against_raycast(input: &str) {
+        let (map, start, path) = resolve(input).unwrap();
+        assert_eq!(path[0], start);
+        assert_eq!(enclosed_cnt(&path), enclosed_cnt_raycast(&map, &path));
+    }
+
+    #[test]
+    fn test_render() {
+        let input = "...........
+            .S-------7.
+            .|F-----7|.
+            .||.-...||.
+            .||.....||.
+            .|L-7.F-J|.
+            .|.7|.|..|.
+            .L--J.L--J.
+            ...........";
+        let (map, start_pos, path) = resolve(input).unwrap();
+        assert_eq!(
+            render(&map, start_pos, &path, Style::Plain),
+            "OOOOOOOOOOO
+O┏───────┐O
+O│┌─────┐│O
+O││OOOOO││O
+O││OOOOO││O
+O│└─┐O┌─┘│O
+O│II│O│II│O
+O└──┘O└──┘O
+OOOOOOOOOOO
+"
+        );
+
+        let ansi = render(&map, start_pos, &path, Style::Ansi);
+        assert!(ansi.starts_with("\x1b[2mO\x1b[0m"));
+        assert!(ansi.contains("\x1b[1;33m┏\x1b[0m"));
+        assert_eq!(ansi.matches("\x1b[31mI").count(), 4);
+    }
+
+    #[test]
+    fn test_pick_matches_raycast() {
+        check_against_raycast(
+            ".F----7F7F7F7F-7....
+            .|F--7||||||||FJ....
+            .||.FJ||||||||L7....
+            FJL7L7LJLJ||LJ.L-7..
+            L--J.L7...LJS7F-7L7.
+            ....F-J..F7FJ|L7L7L7
+            ....L7.F7||L7|.L7L7|
+            .....|FJLJ|FJ|F7|.LJ
+            ....FJL-7.||.||||...
+            ....L---J.LJ.LJLJ...",
+        );
+        check_against_raycast(
+            "FF7FSF7F7F7F7F7F---7
+            L|LJ||||||||||||F--J
+            FL-7LJLJ||||||LJL-77
+            F--JF--7||LJLJ7F7FJ-
+            L---JF-JLJ.||-FJLJJ7
+            |F|F-JF---7F7-L7L|7|
+            |FFJF7L7F-JF7|JL---7
+            7-L-JL7||F7|L7F-7F7|
+            L.L7LFJ|||||FJL7||LJ
+            L7JLJL-JLJLJL--JLJ.L",
+        );
+        check_against_raycast(include_str!("../input"));
+    }
+
+    #[test]
+    fn test_junk_around_start() {
+        // the '-' west and the '|' north of S point at it, but aren't part of the loop
+        let input = "..|..
+            .-S-7
+            ..|.|
+            ..L-J";
+        let (map, start_pos, path) = resolve(input).unwrap();
+        assert_eq!(map.get(start_pos), Some(SOUTH | EAST));
+        assert_eq!(path.len(), 8);
+        assert_eq!(part1_and_2(input), Ok((4, 1)));
+    }
+
+    #[test]
+    fn test_start_errors() {
+        assert_eq!(part1_and_2("F-7\n|.|\nL-J"), Err(MapError::NoStart));
+        assert_eq!(part1_and_2("S-S\n|.|\nL-J"), Err(MapError::MultipleStarts));
+        assert_eq!(part1_and_2(".S-\n.|.\n..."), Err(MapError::NoLoop));
+        // two loops meet at S, either can be closed through it
+        let figure_eight = "F7...
+            |L7..
+            L-S-7
+            ..L7|
+            ...LJ";
+        assert_eq!(
+            part1_and_2(figure_eight),
+            Err(MapError::AmbiguousStart(vec![NORTH | WEST, SOUTH | EAST]))
+        );
+    }
+
+    #[test]
+    fn test_find_loops() {
+        let input = "S7.F--7
+            LJ.|F7|
+            ...|LJ|
+            ...L--J
+            F-7....
+            |.L-7.-
+            L---J..";
+        let (map, _, _) = resolve(input).unwrap();
+        let loops = find_loops(&map);
+        let summary: Vec<_> = loops
+            .iter()
+            .map(|l| (l.path[0], l.path.len(), l.enclosed))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ([0, 0], 4, 0),
+                // the loop at [4, 1] inside does not count
+                ([3, 0], 12, 0),
+                ([4, 1], 4, 0),
+                ([0, 4], 12, 1)
+            ]
+        );
+    }
+}
@@ -1,46 +1,76 @@
-use std::collections::HashSet;
-
 fn main() {
     let input = include_str!("../input");
+    // distance between two numbered galaxies, e.g. `day11 5 9`
+    let args: Vec<usize> = std::env::args()
+        .skip(1)
+        .filter_map(|a| a.parse().ok())
+        .collect();
+    if let [a, b] = args[..] {
+        dbg!(Universe::new(input, 2).distance(a, b));
+        dbg!(Universe::new(input, 1000000).distance(a, b));
+    }
     dbg!(part1(input));
     dbg!(part2(input));
 }
 
-fn part1(input: &str) -> i64 {
-    let map = parse_dilate(input, 1);
+fn part1(input: &str) -> i128 {
+    Universe::new(input, 2).distance_sum()
+}
 
-    let mut total_dist = 0_i64;
-    for galaxy in &map {
-        for other in &map {
-            total_dist += (galaxy[0]-other[0]).abs() + (galaxy[1]-other[1]).abs();
-        }
-    }
-    // this counts [A,B] as well as [B,A] so divide by 2
-    total_dist/2
+fn part2(input: &str) -> i128 {
+    Universe::new(input, 1000000).distance_sum()
 }
 
-fn part2(input: &str) -> i64 {
-    let map = parse_dilate(input, 1000000-1);
+struct Universe {
+    /// Expanded positions, galaxy `n` is at index `n - 1` (numbered in reading order).
+    galaxies: Vec<[i64; 2]>,
+}
 
-    let mut total_dist = 0_i64;
-    for galaxy in &map {
-        for other in &map {
-            total_dist += (galaxy[0]-other[0]).abs() + (galaxy[1]-other[1]).abs();
+impl Universe {
+    /// Every empty row and column is replaced by `expansion` of them.
+    fn new(input: &str, expansion: i64) -> Self {
+        Universe {
+            galaxies: parse_dilate(input, expansion - 1),
         }
     }
-    // this counts [A,B] as well as [B,A] so divide by 2
-    total_dist/2
+
+    /// Shortest path between the galaxies numbered `a` and `b`.
+    fn distance(&self, a: usize, b: usize) -> Option<i64> {
+        let a = self.galaxies.get(a.checked_sub(1)?)?;
+        let b = self.galaxies.get(b.checked_sub(1)?)?;
+        Some((a[0] - b[0]).abs() + (a[1] - b[1]).abs())
+    }
+
+    /// Sum of the distances between all pairs of galaxies.
+    /// The axes are independent, so each one is summed over its sorted coordinates.
+    fn distance_sum(&self) -> i128 {
+        (0..2)
+            .map(|axis| axis_distance_sum(self.galaxies.iter().map(|g| g[axis]).collect()))
+            .sum()
+    }
 }
 
-fn parse_dilate(input: &str, dilate_amt: i64) -> HashSet<[i64; 2]> {
+fn axis_distance_sum(mut coords: Vec<i64>) -> i128 {
+    coords.sort_unstable();
+    let mut prefix = 0_i128;
+    let mut total = 0_i128;
+    for (idx, c) in coords.into_iter().enumerate() {
+        // distance from c to each of the idx smaller coordinates before it
+        total += c as i128 * idx as i128 - prefix;
+        prefix += c as i128;
+    }
+    total
+}
+
+fn parse_dilate(input: &str, dilate_amt: i64) -> Vec<[i64; 2]> {
     let mut xcounts = vec![0; input.lines().next().unwrap().len()];
     let mut ycounts = vec![0; input.lines().count()];
 
-    let mut result = HashSet::new();
+    let mut result = vec![];
     for (y, line) in input.lines().enumerate() {
         for (x, ch) in line.trim().char_indices() {
             if ch == '#' {
-                result.insert([x as i64, y as i64]);
+                result.push([x as i64, y as i64]);
                 xcounts[x] += 1;
                 ycounts[y] += 1;
             }
@@ -74,22 +104,20 @@ fn parse_dilate(input: &str, dilate_amt: i64) -> HashSet<[i64; 2]> {
             .collect()
     };
 
-    let result = result.iter().map(|[x,y]| {
-        // add dilation
-        [*x + xoffset[*x as usize], *y + yoffset[*y as usize]]
-    }).collect();
-
     result
+        .iter()
+        .map(|[x, y]| {
+            // add dilation
+            [*x + xoffset[*x as usize], *y + yoffset[*y as usize]]
+        })
+        .collect()
 }
 
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_part1() {
-        let input = "...#......
+    const EXAMPLE: &str = "...#......
         .......#..
         #.........
         ..........
@@ -99,6 +127,34 @@ mod tests {
         ..........
         .......#..
         #...#.....";
-        assert_eq!(part1(input), 374);
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(part1(EXAMPLE), 374);
+    }
+
+    #[test]
+    fn test_expansion() {
+        assert_eq!(Universe::new(EXAMPLE, 10).distance_sum(), 1030);
+        assert_eq!(Universe::new(EXAMPLE, 100).distance_sum(), 8410);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_distance() {
+        let universe = Universe::new(EXAMPLE, 2);
+        assert_eq!(universe.distance(5, 9), Some(9));
+        assert_eq!(universe.distance(1, 7), Some(15));
+        assert_eq!(universe.distance(3, 6), Some(17));
+        assert_eq!(universe.distance(8, 9), Some(5));
+        assert_eq!(universe.distance(0, 9), None);
+        assert_eq!(universe.distance(1, 10), None);
+
+        // the prefix sums agree with summing every pair
+        let n = universe.galaxies.len();
+        let pairwise: i64 = (1..=n)
+            .flat_map(|a| (a + 1..=n).map(move |b| (a, b)))
+            .map(|(a, b)| universe.distance(a, b).unwrap())
+            .sum();
+        assert_eq!(pairwise as i128, universe.distance_sum());
+    }
+}
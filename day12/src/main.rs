@@ -2,30 +2,99 @@ use std::collections::HashMap;
 
 fn main() {
     let input = include_str!("../input");
-    dbg!(part1(input));
-    dbg!(part2(input));
+    let args: Vec<String> = std::env::args().collect();
+    if let Some(pos) = args.iter().position(|arg| arg == "--nonogram") {
+        let nonogram = parse_nonogram(&std::fs::read_to_string(&args[pos + 1]).unwrap());
+        match nonogram.solve().unwrap() {
+            Solution::Unique(grid) => print!("unique solution:\n{}", render_grid(&grid)),
+            Solution::Multiple(a, b) => print!(
+                "multiple solutions, for example:\n{}\n{}",
+                render_grid(&a),
+                render_grid(&b)
+            ),
+            Solution::None => println!("no solution"),
+        }
+        return;
+    }
+    if std::env::args().any(|arg| arg == "--explain") {
+        for puz in parse(input) {
+            print!("{}", explain(&puz, 3).unwrap());
+        }
+    }
+    if let Some(pos) = args.iter().position(|arg| arg == "--unfold") {
+        let max_factor = args[pos + 1].parse().unwrap();
+        let joiner = args.get(pos + 2).map_or("?", |j| j.as_str());
+        let mut sums = vec![0u128; max_factor];
+        for puz in parse(input) {
+            let counts = counts_per_factor(&puz, max_factor, joiner).unwrap();
+            for (sum, cnt) in sums.iter_mut().zip(counts) {
+                *sum = sum.checked_add(cnt).expect("sum overflows u128");
+            }
+        }
+        for (factor, sum) in sums.iter().enumerate() {
+            println!("unfolded {}x: {sum}", factor + 1);
+        }
+    }
+    dbg!(part1(input).unwrap());
+    dbg!(part2(input).unwrap());
 }
 
-fn part1(input: &str) -> usize {
-    let puzs = parse(input);
-    puzs.iter()
-        .map(|puz| count_ways(puz.map.as_bytes(), &puz.reqs, &mut HashMap::new()))
-        .sum()
+fn part1(input: &str) -> Result<u128, Overflow> {
+    sum_unfolded(input, 1, "?")
+}
+
+fn part2(input: &str) -> Result<u128, Overflow> {
+    sum_unfolded(input, 5, "?")
+}
+
+/// A count of arrangements doesn't fit into a `u128`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+struct Overflow;
+
+/// Counts of arrangements, keyed by the lengths of the remaining map and
+/// requirements. As those are suffixes of one record, a memo can be reused for
+/// every record ending in the same way, e.g. all unfoldings of a record.
+type Memo = HashMap<(usize, usize), u128>;
+
+fn sum_unfolded(input: &str, factor: usize, joiner: &str) -> Result<u128, Overflow> {
+    parse(input)
+        .iter()
+        .map(|puz| {
+            let puz = unfold(puz, factor, joiner);
+            count_ways(puz.map.as_bytes(), &puz.reqs, &mut Memo::new())
+        })
+        .try_fold(0u128, |sum, cnt| sum.checked_add(cnt?).ok_or(Overflow))
 }
 
-fn part2(input: &str) -> usize {
-    let mut puzs = parse(input);
-    for puz in puzs.iter_mut() {
-        puz.map = [puz.map.as_str(); 5].join("?");
-        puz.reqs = puz.reqs.repeat(5);
+/// `factor` copies of the record separated by `joiner`, with the requirements repeated to match.
+fn unfold(puz: &Puzzle, factor: usize, joiner: &str) -> Puzzle {
+    Puzzle {
+        map: vec![puz.map.as_str(); factor].join(joiner),
+        reqs: puz.reqs.repeat(factor),
     }
-    puzs.iter()
-        .map(|puz| count_ways(puz.map.as_bytes(), &puz.reqs, &mut HashMap::new()))
-        .sum()
+}
+
+/// Counts for every unfold factor from 1 to `max_factor`, sharing one memo.
+/// A record unfolded `k` times is a suffix of the one unfolded `max_factor` times.
+fn counts_per_factor(puz: &Puzzle, max_factor: usize, joiner: &str) -> Result<Vec<u128>, Overflow> {
+    let full = unfold(puz, max_factor, joiner);
+    let (map, reqs) = (full.map.as_bytes(), full.reqs.as_slice());
+    let mut memo = Memo::new();
+    (1..=max_factor)
+        .map(|factor| {
+            let map_len = factor * puz.map.len() + (factor - 1) * joiner.len();
+            let reqs_len = factor * puz.reqs.len();
+            count_ways(
+                &map[map.len() - map_len..],
+                &reqs[reqs.len() - reqs_len..],
+                &mut memo,
+            )
+        })
+        .collect()
 }
 
 fn trim_dots(mut map: &[u8]) -> &[u8] {
-    while let Some(b'.') = map.get(0) {
+    while let Some(b'.') = map.first() {
         map = &map[1..];
     }
     map
@@ -33,14 +102,14 @@ fn trim_dots(mut map: &[u8]) -> &[u8] {
 
 fn consume_req(mut map: &[u8], mut req: usize) -> Option<&[u8]> {
     while req > 0 {
-        if let Some(b'#' | b'?') = map.get(0) {
+        if let Some(b'#' | b'?') = map.first() {
             map = &map[1..];
             req -= 1;
         } else {
             return None;
         }
     }
-    match map.get(0) {
+    match map.first() {
         Some(b'.') => Some(map),
         None => Some(map),
         Some(b'?') => {
@@ -52,24 +121,20 @@ fn consume_req(mut map: &[u8], mut req: usize) -> Option<&[u8]> {
     }
 }
 
-fn count_ways<'a>(
-    map: &'a [u8],
-    reqs: &'a [usize],
-    memo: &mut HashMap<(&'a [u8], &'a [usize]), usize>,
-) -> usize {
+fn count_ways(map: &[u8], reqs: &[usize], memo: &mut Memo) -> Result<u128, Overflow> {
     let map = trim_dots(map);
 
-    if let Some(result) = memo.get(&(map, reqs)) {
-        return *result;
+    if let Some(result) = memo.get(&(map.len(), reqs.len())) {
+        return Ok(*result);
     }
 
-    let result = if let Some(req) = reqs.get(0) {
+    let result = if let Some(req) = reqs.first() {
         // there's a requirement to fulfill
-        match map.get(0) {
+        match map.first() {
             Some(b'#') => {
                 // consume the requirement
                 if let Some(next) = consume_req(map, *req) {
-                    count_ways(next, &reqs[1..], memo)
+                    count_ways(next, &reqs[1..], memo)?
                 } else {
                     0
                 }
@@ -77,27 +142,264 @@ fn count_ways<'a>(
             Some(b'?') => {
                 // maybe consume the requirement
                 let maybe_yes = if let Some(next) = consume_req(map, *req) {
-                    count_ways(next, &reqs[1..], memo)
+                    count_ways(next, &reqs[1..], memo)?
                 } else {
                     0
                 };
-                let maybe_no = count_ways(&map[1..], reqs, memo);
-                maybe_yes + maybe_no
+                let maybe_no = count_ways(&map[1..], reqs, memo)?;
+                maybe_yes.checked_add(maybe_no).ok_or(Overflow)?
             }
             Some(_) => unimplemented!(),
             None => 0,
         }
     } else {
         // could be ? left, but we better not have any #
-        if map.iter().any(|c| *c == b'#') {
+        if map.contains(&b'#') {
             0
         } else {
             1
         }
     };
 
-    memo.insert((map, reqs), result);
-    result
+    memo.insert((map.len(), reqs.len()), result);
+    Ok(result)
+}
+
+/// All arrangements of a record, lazily produced in lexicographic order ('#' before '.').
+struct Arrangements<'a> {
+    map: &'a [u8],
+    reqs: &'a [usize],
+    memo: Memo,
+    total: u128,
+    next: u128,
+}
+
+impl<'a> Arrangements<'a> {
+    fn new(puz: &'a Puzzle) -> Result<Self, Overflow> {
+        let (map, reqs) = (puz.map.as_bytes(), puz.reqs.as_slice());
+        let mut memo = Memo::new();
+        let total = count_ways(map, reqs, &mut memo)?;
+        Ok(Arrangements {
+            map,
+            reqs,
+            memo,
+            total,
+            next: 0,
+        })
+    }
+
+    /// The arrangement with index `n`, without producing the ones before it.
+    fn get(&mut self, mut n: u128) -> Option<String> {
+        if n >= self.total {
+            return None;
+        }
+        let (mut map, mut reqs) = (self.map, self.reqs);
+        let mut out = Vec::with_capacity(map.len());
+        // follows the same choices as count_ways, taking the '#' branch first
+        loop {
+            let trimmed = trim_dots(map);
+            out.resize(out.len() + map.len() - trimmed.len(), b'.');
+            map = trimmed;
+
+            let Some(req) = reqs.first() else {
+                out.resize(out.len() + map.len(), b'.');
+                return Some(String::from_utf8(out).unwrap());
+            };
+            let place = consume_req(map, *req)
+                // no more than the total, which didn't overflow
+                .map(|next| (next, count_ways(next, &reqs[1..], &mut self.memo).unwrap()))
+                .filter(|(_, cnt)| *cnt > 0);
+            match place {
+                Some((next, cnt)) if map[0] == b'#' || n < cnt => {
+                    out.resize(out.len() + req, b'#');
+                    // the separating cell, if the run doesn't end the record
+                    out.resize(out.len() + map.len() - next.len() - req, b'.');
+                    map = next;
+                    reqs = &reqs[1..];
+                }
+                _ => {
+                    // n < total, so this is a '?' which may be a '.'
+                    n -= place.map_or(0, |(_, cnt)| cnt);
+                    out.push(b'.');
+                    map = &map[1..];
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for Arrangements<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let result = self.get(self.next)?;
+        self.next += 1;
+        Some(result)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<String> {
+        self.next = self.next.saturating_add(n as u128);
+        self.next()
+    }
+}
+
+/// The record with every '?' replaced by '#' or '.' if all arrangements agree on it.
+/// `None` if there is no arrangement at all.
+fn forced(puz: &Puzzle) -> Result<Option<String>, Overflow> {
+    let map = forced_cells(puz.map.as_bytes(), &puz.reqs)?;
+    Ok(map.map(|map| String::from_utf8(map).unwrap()))
+}
+
+fn forced_cells(map: &[u8], reqs: &[usize]) -> Result<Option<Vec<u8>>, Overflow> {
+    let mut map = map.to_vec();
+    let total = count_ways(&map, reqs, &mut Memo::new())?;
+    if total == 0 {
+        return Ok(None);
+    }
+    for idx in 0..map.len() {
+        if map[idx] != b'?' {
+            continue;
+        }
+        map[idx] = b'#';
+        let damaged = count_ways(&map, reqs, &mut Memo::new())?;
+        map[idx] = match damaged {
+            0 => b'.',
+            n if n == total => b'#',
+            _ => b'?',
+        };
+    }
+    Ok(Some(map))
+}
+
+/// Shows the number of arrangements of a record, the cells forced by it,
+/// and the first `limit` arrangements.
+fn explain(puz: &Puzzle, limit: usize) -> Result<String, Overflow> {
+    let mut out = format!("{} {:?}\n", puz.map, puz.reqs);
+    let Some(forced) = forced(puz)? else {
+        out.push_str("  no arrangements\n");
+        return Ok(out);
+    };
+    let mut arrangements = Arrangements::new(puz)?;
+    out += &format!(
+        "  {} arrangements\n  forced: {forced}\n",
+        arrangements.total
+    );
+    for (idx, arrangement) in arrangements.by_ref().take(limit).enumerate() {
+        out += &format!("  #{idx}: {arrangement}\n");
+    }
+    if arrangements.total > limit as u128 {
+        let last = arrangements.total - 1;
+        out += &format!("  ...\n  #{last}: {}\n", arrangements.get(last).unwrap());
+    }
+    Ok(out)
+}
+
+/// A 2D puzzle: every row and column of the grid is a record like the ones above.
+struct Nonogram {
+    rows: Vec<Vec<usize>>,
+    cols: Vec<Vec<usize>>,
+}
+
+type Grid = Vec<Vec<u8>>;
+
+#[derive(Debug, PartialEq, Eq)]
+enum Solution {
+    Unique(Grid),
+    /// Two of the possible solutions.
+    Multiple(Grid, Grid),
+    None,
+}
+
+impl Nonogram {
+    fn solve(&self) -> Result<Solution, Overflow> {
+        let grid = vec![vec![b'?'; self.cols.len()]; self.rows.len()];
+        let mut found = vec![];
+        self.search(grid, &mut found)?;
+        let mut found = found.into_iter();
+        Ok(match (found.next(), found.next()) {
+            (None, _) => Solution::None,
+            (Some(grid), None) => Solution::Unique(grid),
+            (Some(a), Some(b)) => Solution::Multiple(a, b),
+        })
+    }
+
+    /// Collects up to two solutions, guessing a cell whenever the line solver gets stuck.
+    fn search(&self, mut grid: Grid, found: &mut Vec<Grid>) -> Result<(), Overflow> {
+        if found.len() >= 2 || !self.propagate(&mut grid)? {
+            return Ok(());
+        }
+        let unknown = grid
+            .iter()
+            .enumerate()
+            .find_map(|(y, row)| Some((y, row.iter().position(|c| *c == b'?')?)));
+        let Some((y, x)) = unknown else {
+            found.push(grid);
+            return Ok(());
+        };
+        for guess in [b'#', b'.'] {
+            let mut next = grid.clone();
+            next[y][x] = guess;
+            self.search(next, found)?;
+        }
+        Ok(())
+    }
+
+    /// Fixes every cell forced by its row or column until nothing changes.
+    /// Returns false if some line has no arrangement left.
+    fn propagate(&self, grid: &mut Grid) -> Result<bool, Overflow> {
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (y, reqs) in self.rows.iter().enumerate() {
+                let Some(line) = forced_cells(&grid[y], reqs)? else {
+                    return Ok(false);
+                };
+                changed |= line != grid[y];
+                grid[y] = line;
+            }
+            for (x, reqs) in self.cols.iter().enumerate() {
+                let col: Vec<u8> = grid.iter().map(|row| row[x]).collect();
+                let Some(line) = forced_cells(&col, reqs)? else {
+                    return Ok(false);
+                };
+                if line != col {
+                    changed = true;
+                    for (row, cell) in grid.iter_mut().zip(line) {
+                        row[x] = cell;
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Row clues, an empty line, then column clues; one comma separated line each.
+/// An empty row or column is written as `0`.
+fn parse_nonogram(input: &str) -> Nonogram {
+    let clues = |block: &str| -> Vec<Vec<usize>> {
+        block
+            .lines()
+            .map(|line| {
+                line.trim()
+                    .split(',')
+                    .map(|n| n.trim().parse::<usize>().unwrap())
+                    .filter(|n| *n > 0)
+                    .collect()
+            })
+            .collect()
+    };
+    let (rows, cols) = input.trim().split_once("\n\n").unwrap();
+    Nonogram {
+        rows: clues(rows),
+        cols: clues(cols),
+    }
+}
+
+fn render_grid(grid: &Grid) -> String {
+    grid.iter()
+        .map(|row| String::from_utf8_lossy(row) + "\n")
+        .collect()
 }
 
 struct Puzzle {
@@ -132,13 +434,13 @@ mod tests {
         ????.#...#... 4,1,1
         ????.######..#####. 1,6,5
         ?###???????? 3,2,1";
-        assert_eq!(21, part1(input));
+        assert_eq!(Ok(21), part1(input));
     }
 
     #[test]
     fn test_part1_hard() {
         let input = "?###???????? 3,2,1";
-        assert_eq!(10, part1(input));
+        assert_eq!(Ok(10), part1(input));
     }
 
     #[test]
@@ -149,6 +451,165 @@ mod tests {
         ????.#...#... 4,1,1
         ????.######..#####. 1,6,5
         ?###???????? 3,2,1";
-        assert_eq!(525152, part2(input));
+        assert_eq!(Ok(525152), part2(input));
+    }
+
+    fn puzzle(line: &str) -> Puzzle {
+        parse(line).pop().unwrap()
+    }
+
+    #[test]
+    fn test_arrangements() {
+        let puz = puzzle("?###???????? 3,2,1");
+        let all: Vec<_> = Arrangements::new(&puz).unwrap().collect();
+        assert_eq!(
+            all,
+            vec![
+                ".###.##.#...",
+                ".###.##..#..",
+                ".###.##...#.",
+                ".###.##....#",
+                ".###..##.#..",
+                ".###..##..#.",
+                ".###..##...#",
+                ".###...##.#.",
+                ".###...##..#",
+                ".###....##.#",
+            ]
+        );
+        let mut sorted = all.clone();
+        sorted.sort();
+        assert_eq!(all, sorted);
+
+        let puz = puzzle("?#?#?#?#?#?#?#? 1,3,1,6");
+        assert_eq!(
+            Arrangements::new(&puz).unwrap().collect::<Vec<_>>(),
+            vec![".#.###.#.######"]
+        );
+
+        let puz = puzzle("#.? 2");
+        assert_eq!(Arrangements::new(&puz).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_arrangement_index() {
+        let puz = puzzle(".??..??...?##. 1,1,3");
+        let mut arrangements = Arrangements::new(&puz).unwrap();
+        assert_eq!(arrangements.total, 4);
+        assert_eq!(arrangements.get(0).unwrap(), ".#...#....###.");
+        assert_eq!(arrangements.get(3).unwrap(), "..#...#...###.");
+        assert_eq!(arrangements.get(4), None);
+        assert_eq!(arrangements.nth(2).unwrap(), "..#..#....###.");
+        assert_eq!(arrangements.next().unwrap(), "..#...#...###.");
+        assert_eq!(arrangements.next(), None);
+    }
+
+    #[test]
+    fn test_forced() {
+        assert_eq!(
+            forced(&puzzle("???.### 1,1,3")).unwrap().unwrap(),
+            "#.#.###"
+        );
+        assert_eq!(
+            forced(&puzzle("?###???????? 3,2,1")).unwrap().unwrap(),
+            ".###.???????"
+        );
+        assert_eq!(
+            forced(&puzzle("????.#...#... 4,1,1")).unwrap().unwrap(),
+            "####.#...#..."
+        );
+        assert_eq!(forced(&puzzle("??? 2")).unwrap().unwrap(), "?#?");
+        assert_eq!(forced(&puzzle("#.# 2")), Ok(None));
+    }
+
+    fn grid(rows: &str) -> Grid {
+        rows.lines()
+            .map(|row| row.trim().bytes().collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_nonogram_unique() {
+        let nonogram = parse_nonogram(
+            "1,1
+            5
+            1,1,1
+            3
+            1
+
+            2
+            2,1
+            4
+            2,1
+            2",
+        );
+        let solution = grid(
+            ".#.#.
+            #####
+            #.#.#
+            .###.
+            ..#..",
+        );
+        assert_eq!(
+            nonogram.solve().unwrap(),
+            Solution::Unique(solution.clone())
+        );
+        assert_eq!(
+            render_grid(&solution),
+            ".#.#.\n#####\n#.#.#\n.###.\n..#..\n"
+        );
+    }
+
+    #[test]
+    fn test_nonogram_multiple() {
+        // the line solver can't decide anything here, both diagonals fit
+        let nonogram = parse_nonogram("1\n1\n\n1\n1");
+        assert_eq!(
+            nonogram.solve().unwrap(),
+            Solution::Multiple(grid("#.\n.#"), grid(".#\n#."))
+        );
+    }
+
+    #[test]
+    fn test_nonogram_none() {
+        let nonogram = parse_nonogram("2\n0\n\n1\n0");
+        assert_eq!(nonogram.solve().unwrap(), Solution::None);
+    }
+
+    #[test]
+    fn test_counts_per_factor() {
+        let puz = puzzle(".??..??...?##. 1,1,3");
+        assert_eq!(
+            counts_per_factor(&puz, 5, "?"),
+            Ok(vec![4, 32, 256, 2048, 16384])
+        );
+        let puz = puzzle("?###???????? 3,2,1");
+        assert_eq!(counts_per_factor(&puz, 5, "?").unwrap()[4], 506250);
+    }
+
+    #[test]
+    fn test_joiner() {
+        let puz = puzzle("??? 1");
+        assert_eq!(counts_per_factor(&puz, 2, "."), Ok(vec![3, 11]));
+        assert_eq!(counts_per_factor(&puz, 2, ""), Ok(vec![3, 10]));
+        assert_eq!(sum_unfolded("??? 1", 2, "."), Ok(11));
+    }
+
+    #[test]
+    fn test_overflow() {
+        // C(301, 100) ways to place 100 single springs in 400 cells
+        let puz = Puzzle {
+            map: "?".repeat(400),
+            reqs: vec![1; 100],
+        };
+        assert_eq!(
+            count_ways(puz.map.as_bytes(), &puz.reqs, &mut Memo::new()),
+            Err(Overflow)
+        );
+        // C(120, 60) needs more than a u64
+        assert_eq!(
+            sum_unfolded("?? 1", 60, "?"),
+            Ok(96614908840363322603893139521372656)
+        );
     }
 }
@@ -4,61 +4,242 @@ fn main() {
     dbg!(part2(input));
 }
 
-fn part1(input: &str) -> usize {
+fn part1(input: &str) -> Option<usize> {
     input.split("\n\n").map(|input| score(input, 0)).sum()
 }
 
-fn part2(input: &str) -> usize {
+fn part2(input: &str) -> Option<usize> {
     input.split("\n\n").map(|input| score(input, 1)).sum()
 }
 
-fn score(input: &str, smudges: u32) -> usize {
-    let (rows, cols) = parse(input);
+/// Summary of the first reflection with exactly `smudges` smudges, rows before columns.
+fn score(input: &str, smudges: usize) -> Option<usize> {
+    reflections(input, smudges)
+        .into_iter()
+        .find(|refl| refl.smudges.len() == smudges)
+        .map(|refl| match refl.axis {
+            Axis::Horizontal => 100 * refl.pos,
+            Axis::Vertical => refl.pos,
+        })
+}
 
-    for (nums, scale) in [(&rows, 100), (&cols, 1)] {
-        for start in 1..nums.len() {
-            let mut second = start;
-            let mut first = second - 1;
-            let mut diffs = 0;
-            let found = loop {
-                diffs += (nums[first] ^ nums[second]).count_ones();
-                if diffs > smudges {
-                    break false;
-                }
-                if first == 0 {
-                    break diffs == smudges;
-                }
-                first -= 1;
-                second += 1;
-                if second == nums.len() {
-                    break diffs == smudges;
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Axis {
+    /// Mirror line between two rows.
+    Horizontal,
+    /// Mirror line between two columns.
+    Vertical,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct Reflection {
+    axis: Axis,
+    /// Number of rows above or columns left of the mirror line.
+    pos: usize,
+    /// `(row, col)` of each cell before the mirror line which has to be fixed
+    /// for the reflection to be perfect. Fixing its mirror image works as well.
+    smudges: Vec<(usize, usize)>,
+}
+
+/// Every mirror line needing at most `max_smudges` cells fixed, rows before columns.
+fn reflections(input: &str, max_smudges: usize) -> Vec<Reflection> {
+    let (rows, cols) = parse(input);
+    let mut result = vec![];
+    for (lines, axis) in [(&rows, Axis::Horizontal), (&cols, Axis::Vertical)] {
+        'start: for start in 1..lines.len() {
+            let mut smudges = vec![];
+            let pairs = (0..start).rev().zip(start..lines.len());
+            for (first, second) in pairs {
+                if smudges.len() + lines[first].diff(&lines[second]) as usize > max_smudges {
+                    continue 'start;
                 }
-            };
-            if found {
-                return scale * start;
+                smudges.extend(
+                    lines[first]
+                        .diff_cells(&lines[second])
+                        .map(|cell| match axis {
+                            Axis::Horizontal => (first, cell),
+                            Axis::Vertical => (cell, first),
+                        }),
+                );
             }
+            smudges.sort();
+            result.push(Reflection {
+                axis,
+                pos: start,
+                smudges,
+            });
         }
     }
-    unimplemented!()
+    result
 }
 
-fn parse(input: &str) -> (Vec<usize>, Vec<usize>) {
+/// One row or column of a pattern, a bit per cell, as long as it needs to be.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Bits(Vec<u64>);
+
+impl Bits {
+    fn from_cells(cells: impl Iterator<Item = bool>) -> Self {
+        let mut words = vec![];
+        for (idx, cell) in cells.enumerate() {
+            if idx % 64 == 0 {
+                words.push(0);
+            }
+            if cell {
+                *words.last_mut().unwrap() |= 1 << (idx % 64);
+            }
+        }
+        Bits(words)
+    }
+
+    /// Number of cells which differ.
+    fn diff(&self, other: &Bits) -> u32 {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum()
+    }
+
+    /// Positions of the cells which differ.
+    fn diff_cells<'a>(&'a self, other: &'a Bits) -> impl Iterator<Item = usize> + 'a {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .enumerate()
+            .flat_map(|(word, (a, b))| {
+                let mut diff = a ^ b;
+                std::iter::from_fn(move || {
+                    if diff == 0 {
+                        return None;
+                    }
+                    let bit = diff.trailing_zeros() as usize;
+                    diff &= diff - 1;
+                    Some(word * 64 + bit)
+                })
+            })
+    }
+}
+
+fn parse(input: &str) -> (Vec<Bits>, Vec<Bits>) {
     let lines: Vec<_> = input.lines().map(str::as_bytes).collect();
     let rows: Vec<_> = lines
         .iter()
-        .map(|&line| {
-            line.iter().fold(0usize, |acc, n| {
-                (acc << 1) | (if *n == b'#' { 1 } else { 0 })
-            })
-        })
+        .map(|&line| Bits::from_cells(line.iter().map(|n| *n == b'#')))
         .collect();
 
     let cols: Vec<_> = (0..lines[0].len())
-        .map(|col| {
-            (0..lines.len()).fold(0usize, |acc, row| {
-                (acc << 1) | if lines[row][col] == b'#' { 1 } else { 0 }
-            })
-        })
+        .map(|col| Bits::from_cells((0..lines.len()).map(|row| lines[row][col] == b'#')))
         .collect();
     (rows, cols)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#";
+
+    #[test]
+    fn test_parts() {
+        assert_eq!(part1(EXAMPLE), Some(405));
+        assert_eq!(part2(EXAMPLE), Some(400));
+    }
+
+    #[test]
+    fn test_wide_pattern() {
+        // 200 columns, mirrored around the one after column 60; the two rows
+        // differ only at columns 5 and 114 and agree on the last 80, all cut
+        // from the example patterns strung together
+        let cells: Vec<u8> = EXAMPLE.bytes().filter(|&b| b != b'\n').collect();
+        let a = cells[..60].to_vec();
+        let tail = cells[cells.len() - 80..].to_vec();
+        let mut b = a.clone();
+        b[5] = if b[5] == b'#' { b'.' } else { b'#' };
+        let row = |half: &[u8]| {
+            let mut row = half.to_vec();
+            row.extend(half.iter().rev());
+            row.extend(&tail);
+            String::from_utf8(row).unwrap()
+        };
+        let pattern = format!("{}\n{}", row(&a), row(&b));
+        assert_eq!(pattern.lines().next().unwrap().len(), 200);
+
+        let (rows, cols) = parse(&pattern);
+        assert_eq!(rows[0].diff(&rows[1]), 2);
+        assert_eq!(cols.len(), 200);
+        assert_eq!(score(&pattern, 0), Some(60));
+        // allowing two differing cells, the rows mirror each other
+        assert_eq!(score(&pattern, 2), Some(100));
+        assert_eq!(
+            reflections(&pattern, 2)[0],
+            Reflection {
+                axis: Axis::Horizontal,
+                pos: 1,
+                smudges: vec![(0, 5), (0, 114)],
+            }
+        );
+    }
+
+    #[test]
+    fn test_reflections() {
+        let mut patterns = EXAMPLE.split("\n\n");
+        let (first, second) = (patterns.next().unwrap(), patterns.next().unwrap());
+
+        assert_eq!(
+            reflections(first, 0),
+            vec![Reflection {
+                axis: Axis::Vertical,
+                pos: 5,
+                smudges: vec![],
+            }]
+        );
+        assert_eq!(
+            reflections(first, 1),
+            vec![
+                Reflection {
+                    axis: Axis::Horizontal,
+                    pos: 3,
+                    smudges: vec![(0, 0)],
+                },
+                Reflection {
+                    axis: Axis::Vertical,
+                    pos: 5,
+                    smudges: vec![],
+                },
+            ]
+        );
+        let fix = reflections(second, 1)
+            .into_iter()
+            .find(|refl| refl.smudges.len() == 1)
+            .unwrap();
+        assert_eq!(
+            (fix.axis, fix.pos, fix.smudges),
+            (Axis::Horizontal, 1, vec![(0, 4)])
+        );
+    }
+
+    #[test]
+    fn test_no_reflection() {
+        let pattern = "#..\n.#.\n..#";
+        assert_eq!(reflections(pattern, 0), vec![]);
+        assert_eq!(score(pattern, 0), None);
+        assert_eq!(part1(&format!("{pattern}\n\n{EXAMPLE}")), None);
+        // a 3x3 identity can't be mirrored by fixing a single cell either
+        assert!(reflections(pattern, 1).is_empty());
+        assert_eq!(reflections(pattern, 2).len(), 4);
+    }
+}
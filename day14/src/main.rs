@@ -1,4 +1,6 @@
 use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
 
 fn main() {
     let input = include_str!("../input");
@@ -13,119 +15,437 @@ enum Tile {
     Space,
 }
 
-fn part1(input: &str) -> usize {
-    let map = parse(input);
-
-    let next = roll_map(map);
-
-    // print_map(&next);
+#[derive(Clone, Debug, Eq, PartialEq, Copy)]
+enum Dir {
+    North,
+    West,
+    South,
+    East,
+}
 
-    score(next)
+fn part1(input: &str) -> usize {
+    let mut map = parse(input);
+    tilt(&mut map, Dir::North);
+    score(&map, Dir::North)
 }
 
 fn part2(input: &str, spin_cnt: usize) -> usize {
-    let map = parse(input);
+    let mut board = Bitboard::new(&parse(input));
 
+    // only a fingerprint of each state is kept, not the whole platform
     let mut prev_states = HashMap::new();
-    
-    let mut next = map;
-    let mut skipped_rolls = 0;
-    for n in 1.. {
-        next = spin_map(roll_map(next));
-        if n + skipped_rolls == 4 * spin_cnt {
-            return score(next);
-        }
-
-        if let Some(prev_n) = prev_states.get(&next) {
-            // print_map(&next);
+    let mut n = 0;
+    while n < spin_cnt {
+        board.spin_cycle();
+        n += 1;
+        if let Some(prev_n) = prev_states.insert(board.fingerprint(), n) {
+            // skip all the repetitions that fit into the remaining cycles
             let loop_len = n - prev_n;
-            let remaining = 4 * spin_cnt - n;
-            skipped_rolls = (remaining / loop_len) * loop_len;
+            n += (spin_cnt - n) / loop_len * loop_len;
+            prev_states.clear();
         }
-        prev_states.insert(next.clone(), n);
     }
+    board.north_load()
+}
+
+#[cfg(test)]
+fn spin_cycle(map: &mut [Vec<Tile>]) {
+    for dir in [Dir::North, Dir::West, Dir::South, Dir::East] {
+        tilt(map, dir);
+    }
+}
 
-    unimplemented!()
+/// Position of the `step`th tile counted from the `dir` edge, in row or column `lane`.
+fn lane_pos(dir: Dir, lane: usize, step: usize, height: usize, width: usize) -> (usize, usize) {
+    match dir {
+        Dir::North => (step, lane),
+        Dir::South => (height - 1 - step, lane),
+        Dir::West => (lane, step),
+        Dir::East => (lane, width - 1 - step),
+    }
 }
 
-fn roll_map(map: Vec<Vec<Tile>>) -> Vec<Vec<Tile>> {
+/// Rolls all round rocks as far towards `dir` as they go.
+fn tilt(map: &mut [Vec<Tile>], dir: Dir) {
     use Tile::*;
-    let mut next: Vec<Vec<Tile>> = vec![];
-    for (rowidx, row) in map.iter().enumerate() {
-        let mut nextrow = vec![];
-        for (colidx, tile) in row.iter().enumerate() {
-            match tile {
+    let (height, width) = (map.len(), map[0].len());
+    let (lanes, len) = match dir {
+        Dir::North | Dir::South => (width, height),
+        Dir::West | Dir::East => (height, width),
+    };
+    for lane in 0..lanes {
+        // where the next rock in this lane comes to rest
+        let mut free = 0;
+        for step in 0..len {
+            let (y, x) = lane_pos(dir, lane, step, height, width);
+            match map[y][x] {
+                Cube => free = step + 1,
                 Round => {
                     // rock n roll
-                    let mut idx = rowidx;
-                    while idx > 0 && next[idx-1][colidx] == Space {
-                        idx -= 1;
-                    }
-                    if idx == rowidx {
-                        nextrow.push(Round);
-                    } else {
-                        nextrow.push(Space);
-                        next[idx][colidx] = Round;
-                    }
-                },
-                Cube => nextrow.push(Cube),
-                Space => nextrow.push(Space),
+                    map[y][x] = Space;
+                    let (y, x) = lane_pos(dir, lane, free, height, width);
+                    map[y][x] = Round;
+                    free += 1;
+                }
+                Space => (),
             }
         }
-        next.push(nextrow);
     }
-    next
 }
 
-fn spin_map(map: Vec<Vec<Tile>>) -> Vec<Vec<Tile>> {
-    let mut next = vec![vec![Tile::Space;map.len()]; map.len()];
-    for mapy in 0..map.len() {
-        for mapx in 0..map[0].len() {
-            next[mapx][map.len()-mapy-1] = map[mapy][mapx];
+/// The round rocks as a bitset, one bit per tile, row by row with whole words per row.
+/// Cubes never move, they are only kept as the runs between them along rows and columns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Bitboard {
+    width: usize,
+    height: usize,
+    rounds: Vec<u64>,
+    /// The runs of tiles between cubes which rocks can slide along, along rows.
+    row_runs: Runs,
+    /// The same for the transposed board, where every column is a row.
+    col_runs: Runs,
+}
+
+/// Runs of at least two tiles between cubes, in a bitset holding lanes one after
+/// the other, whole words each.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Runs {
+    /// Runs within one word, as word index and mask, in order.
+    short: Vec<(usize, u64)>,
+    /// Runs over several words, as bit ranges.
+    long: Vec<Range<usize>>,
+}
+
+impl Runs {
+    fn new(lanes: impl Iterator<Item = Vec<Tile>>) -> Self {
+        let mut runs = Runs {
+            short: vec![],
+            long: vec![],
+        };
+        let mut offset = 0;
+        for lane in lanes {
+            let mut start = 0;
+            for (pos, tile) in lane.iter().chain([&Tile::Cube]).enumerate() {
+                if *tile != Tile::Cube {
+                    continue;
+                }
+                let run = offset + start..offset + pos;
+                start = pos + 1;
+                if run.len() < 2 {
+                    continue;
+                }
+                match range_words(run.clone()).collect::<Vec<_>>()[..] {
+                    [word] => runs.short.push(word),
+                    _ => runs.long.push(run),
+                }
+            }
+            offset += lane.len().div_ceil(64) * 64;
+        }
+        runs
+    }
+
+    /// Moves the rocks of every run to its start, or its end.
+    /// A run just needs its rocks counted and set again packed to one side.
+    fn pack(&self, bits: &mut [u64], to_start: bool) {
+        // the mask shifted by the count of rocks leaves just the tiles they go to
+        let packed = |mask: u64, cnt: u32| match to_start {
+            true => mask & !mask.checked_shl(cnt).unwrap_or(0),
+            false => mask & !mask.checked_shr(cnt).unwrap_or(0),
+        };
+        for runs in self.short.chunk_by(|a, b| a.0 == b.0) {
+            let w = runs[0].0;
+            let word = bits[w];
+            bits[w] = runs.iter().fold(word, |out, &(_, mask)| {
+                out & !mask | packed(mask, (word & mask).count_ones())
+            });
+        }
+        for run in &self.long {
+            let cnt = range_words(run.clone())
+                .map(|(w, mask)| (bits[w] & mask).count_ones() as usize)
+                .sum::<usize>();
+            let packed = if to_start {
+                run.start..run.start + cnt
+            } else {
+                run.end - cnt..run.end
+            };
+            for (w, mask) in range_words(run.clone()) {
+                bits[w] &= !mask;
+            }
+            for (w, mask) in range_words(packed) {
+                bits[w] |= mask;
+            }
         }
     }
-    next
 }
 
-fn parse(input: &str) -> Vec<Vec<Tile>> {
-    use Tile::*;
-    let map: Vec<Vec<Tile>> = input.lines().map(|line| {
-        line.chars().map(|ch| match ch {
-            '#' => Cube,
-            'O' => Round,
-            '.' => Space,
-            _ => unimplemented!()
-        }).collect()
-    }).collect();
+impl Bitboard {
+    fn new(map: &[Vec<Tile>]) -> Self {
+        let (height, width) = (map.len(), map[0].len());
+        let stride = width.div_ceil(64);
+        let mut rounds = vec![0; stride * height];
+        for (y, row) in map.iter().enumerate() {
+            for (x, _) in row.iter().enumerate().filter(|(_, t)| **t == Tile::Round) {
+                rounds[y * stride + x / 64] |= 1 << (x % 64);
+            }
+        }
+        Bitboard {
+            width,
+            height,
+            rounds,
+            row_runs: Runs::new(map.iter().cloned()),
+            col_runs: Runs::new((0..width).map(|x| map.iter().map(|row| row[x]).collect())),
+        }
+    }
+
+    fn spin_cycle(&mut self) {
+        for dir in [Dir::North, Dir::West, Dir::South, Dir::East] {
+            self.tilt(dir);
+        }
+    }
+
+    /// West and east pack the runs of each row. North and south do the same on
+    /// the transposed board, where every column is a row.
+    fn tilt(&mut self, dir: Dir) {
+        match dir {
+            Dir::West | Dir::East => self.row_runs.pack(&mut self.rounds, dir == Dir::West),
+            Dir::North | Dir::South => {
+                let mut cols = transpose(&self.rounds, self.height, self.width);
+                self.col_runs.pack(&mut cols, dir == Dir::North);
+                self.rounds = transpose(&cols, self.width, self.height);
+            }
+        }
+    }
 
-    assert_eq!(map.len(), map[0].len());
+    /// 128 bit hash of the round rocks, which are all that changes between states.
+    /// Wide enough that a collision among the states seen is not a concern.
+    fn fingerprint(&self) -> (u64, u64) {
+        let hash = |salt: u8| {
+            let mut hasher = DefaultHasher::new();
+            salt.hash(&mut hasher);
+            self.rounds.hash(&mut hasher);
+            hasher.finish()
+        };
+        (hash(0), hash(1))
+    }
+
+    fn north_load(&self) -> usize {
+        self.rounds
+            .chunks(self.width.div_ceil(64))
+            .enumerate()
+            .map(|(y, row)| {
+                let cnt: u32 = row.iter().map(|w| w.count_ones()).sum();
+                cnt as usize * (self.height - y)
+            })
+            .sum()
+    }
+}
+
+/// Transposes a bit matrix of `rows` lanes with `cols` bits each, 64x64 bits at a time.
+fn transpose(bits: &[u64], rows: usize, cols: usize) -> Vec<u64> {
+    let (stride, out_stride) = (cols.div_ceil(64), rows.div_ceil(64));
+    let mut out = vec![0; out_stride * cols];
+    let mut block = [0u64; 64];
+    for row_block in 0..out_stride {
+        for col_block in 0..stride {
+            for (k, word) in block.iter_mut().enumerate() {
+                let row = row_block * 64 + k;
+                *word = if row < rows {
+                    bits[row * stride + col_block]
+                } else {
+                    0
+                };
+            }
+            transpose64(&mut block);
+            for (k, word) in block.iter().enumerate() {
+                let col = col_block * 64 + k;
+                if col < cols {
+                    out[col * out_stride + row_block] = *word;
+                }
+            }
+        }
+    }
+    out
+}
 
+/// In place, so bit `c` of word `r` ends up as bit `r` of word `c`.
+/// Swaps the off-diagonal halves, then quarters of the halves and so on.
+fn transpose64(a: &mut [u64; 64]) {
+    let mut j = 32;
+    let mut m = 0x0000_0000_ffff_ffffu64;
+    while j != 0 {
+        let mut k = 0;
+        while k < 64 {
+            let t = ((a[k] >> j) ^ a[k + j]) & m;
+            a[k] ^= t << j;
+            a[k + j] ^= t;
+            k = (k + j + 1) & !j;
+        }
+        j >>= 1;
+        m ^= m << j;
+    }
+}
+
+/// Index and mask of every word holding some of the bits in `range`.
+fn range_words(range: Range<usize>) -> impl Iterator<Item = (usize, u64)> {
+    (range.start / 64..range.end.div_ceil(64)).map(move |w| {
+        let lo = range.start.max(w * 64) - w * 64;
+        let hi = range.end.min(w * 64 + 64) - w * 64;
+        let mask = if hi - lo == 64 {
+            !0
+        } else {
+            ((1 << (hi - lo)) - 1) << lo
+        };
+        (w, mask)
+    })
+}
+
+fn parse(input: &str) -> Vec<Vec<Tile>> {
+    use Tile::*;
+    let map: Vec<Vec<Tile>> = input
+        .lines()
+        .map(|line| {
+            line.chars()
+                .map(|ch| match ch {
+                    '#' => Cube,
+                    'O' => Round,
+                    '.' => Space,
+                    _ => unimplemented!(),
+                })
+                .collect()
+        })
+        .collect();
     map
 }
 
 #[allow(unused)]
-fn print_map(next: &Vec<Vec<Tile>>) {
+fn print_map(next: &[Vec<Tile>]) {
     use Tile::*;
     for row in next.iter() {
         for tile in row {
-            print!("{}", match tile {
-                Cube => '#',
-                Round => 'O',
-                Space => '.',
-            });
+            print!(
+                "{}",
+                match tile {
+                    Cube => '#',
+                    Round => 'O',
+                    Space => '.',
+                }
+            );
         }
         println!();
     }
 }
 
-fn score(next: Vec<Vec<Tile>>) -> usize {
-    use Tile::*;
-    // score the map
-    let height = next.len();
+/// Load on the `dir` support beams: each round rock counts its distance to the opposite edge.
+fn score(map: &[Vec<Tile>], dir: Dir) -> usize {
+    let (height, width) = (map.len(), map[0].len());
     let mut score = 0;
-    for (rowidx, row) in next.iter().enumerate() {
-        let rounds = row.iter().filter(|t| **t == Round).count();
-        score += rounds * (height - rowidx);
+    for (y, row) in map.iter().enumerate() {
+        for (x, _) in row.iter().enumerate().filter(|(_, t)| **t == Tile::Round) {
+            score += match dir {
+                Dir::North => height - y,
+                Dir::South => y + 1,
+                Dir::West => width - x,
+                Dir::East => x + 1,
+            };
+        }
     }
     score
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....";
+
+    fn render(map: &[Vec<Tile>]) -> String {
+        map.iter()
+            .map(|row| {
+                row.iter()
+                    .map(|tile| match tile {
+                        Tile::Cube => '#',
+                        Tile::Round => 'O',
+                        Tile::Space => '.',
+                    })
+                    .chain(['\n'])
+                    .collect::<String>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_parts() {
+        assert_eq!(part1(EXAMPLE), 136);
+        assert_eq!(part2(EXAMPLE, 1000000000), 64);
+    }
+
+    #[test]
+    fn test_spin_cycle() {
+        let mut map = parse(EXAMPLE);
+        spin_cycle(&mut map);
+        assert!(render(&map).starts_with(".....#....\n....#...O#\n...OO##...\n"));
+    }
+
+    #[test]
+    fn test_tilt_rectangular() {
+        let input = "O.#O.\n.O..O\n..O#.";
+        let tilted = |dir| {
+            let mut map = parse(input);
+            tilt(&mut map, dir);
+            render(&map)
+        };
+        assert_eq!(tilted(Dir::North), "OO#OO\n..O..\n...#.\n");
+        assert_eq!(tilted(Dir::West), "O.#O.\nOO...\nO..#.\n");
+        assert_eq!(tilted(Dir::South), "..#..\n...O.\nOOO#O\n");
+        assert_eq!(tilted(Dir::East), ".O#.O\n...OO\n..O#.\n");
+
+        let map = parse(input);
+        assert_eq!(score(&map, Dir::North), 3 + 3 + 2 + 2 + 1);
+        assert_eq!(score(&map, Dir::South), 1 + 1 + 2 + 2 + 3);
+        assert_eq!(score(&map, Dir::West), 5 + 2 + 4 + 1 + 3);
+        assert_eq!(score(&map, Dir::East), 1 + 4 + 2 + 5 + 3);
+    }
+
+    #[test]
+    fn test_bitboard_matches_grid() {
+        // the example 13 across and 7 down, every other copy mirrored: 130 columns,
+        // so rows span three words, and cube-free rows and columns run over several
+        let input: String = (0..70)
+            .map(|y| {
+                let row = EXAMPLE.lines().nth(y % 10).unwrap();
+                let mirrored = row.chars().rev().collect::<String>();
+                let copies = (0..13).map(|x| {
+                    if (x + y / 10) % 2 == 0 {
+                        row
+                    } else {
+                        &mirrored
+                    }
+                });
+                copies.chain(["\n"]).collect::<String>()
+            })
+            .collect();
+
+        let mut map = parse(&input);
+        let mut board = Bitboard::new(&map);
+        for dir in [Dir::North, Dir::East, Dir::South, Dir::West, Dir::North] {
+            tilt(&mut map, dir);
+            board.tilt(dir);
+            assert_eq!(board, Bitboard::new(&map), "after tilting {dir:?}");
+        }
+        for _ in 0..3 {
+            spin_cycle(&mut map);
+            board.spin_cycle();
+        }
+        assert_eq!(board, Bitboard::new(&map));
+        assert_eq!(board.north_load(), score(&map, Dir::North));
+    }
+}
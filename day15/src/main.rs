@@ -1,86 +1,300 @@
-use std::collections::VecDeque;
+use std::borrow::Borrow;
+use std::fmt::{Display, Write};
+use std::ops::Index;
 
 fn main() {
     let input = include_str!("../input");
     dbg!(part1(input));
-    dbg!(part2(input));
+    dbg!(part2(input).unwrap());
+    if std::env::args().any(|arg| arg == "--trace") {
+        for line in trace(parse_steps(input).unwrap()) {
+            println!("{line}");
+        }
+    }
 }
 
 fn part1(input: &str) -> usize {
-    input.trim().split(",").map(hash).sum::<usize>()
+    input
+        .trim()
+        .split(',')
+        .map(|step| hash(step.as_bytes()) as usize)
+        .sum()
 }
 
-fn hash(input: &str) -> usize {
+/// The Holiday ASCII String Helper algorithm.
+fn hash(input: &[u8]) -> u8 {
     let mut acc = 0u8;
-    for ch in input.as_bytes() {
+    for ch in input {
         acc = acc.wrapping_add(*ch).wrapping_mul(17);
     }
-    acc as usize
+    acc
 }
 
-fn part2(input: &str) -> usize {
-    
-    #[derive(Debug,Clone)]
-    struct LensLabel<'a> {
-        label: &'a str,
-        focal: &'a str,
-    }
-    let mut boxes: Vec<VecDeque<LensLabel>> = vec![VecDeque::new(); 256];
-
-    for instr in input.trim().split(",") {
-        let (label, op, focal) = {
-            let mut parser = instr.split_inclusive(&['-', '=']);
-            let label_op = parser.next().unwrap();
-            let (label, op) = label_op.split_at(label_op.len()-1);
-            (label, op, parser.next())
-        };
-        let boxnum = hash(label);
-        match op {
-            "=" => {
-                let boxn = &mut boxes[boxnum];
-                let found = boxn.iter_mut().any(|lens| {
-                    if lens.label == label {
-                        lens.focal = focal.unwrap();
-                        true
-                    } else {
-                        false
-                    }
-                });
-                if !found {
-                    boxn.push_back(LensLabel {
-                        label,
-                        focal: focal.unwrap(),
-                    });
-                }
-            },
-            "-" => {
-                let boxn = &mut boxes[boxnum];
-                if let Some(n) = boxn.iter().position(|lens| lens.label == label) {
-                    boxn.drain(n..=n);
-                }
-            },
-            _ => unimplemented!(),
+/// Map with 256 boxes picked by `hash`, every box keeps its entries in insertion order.
+#[derive(Debug, Clone)]
+struct HolidayMap<K, V> {
+    boxes: Vec<Vec<(K, V)>>,
+}
+
+impl<K: AsRef<[u8]> + Eq, V> HolidayMap<K, V> {
+    fn new() -> Self {
+        HolidayMap {
+            boxes: (0..256).map(|_| vec![]).collect(),
         }
     }
 
-    // score it
-    let mut score = 0;
-    for boxn in 0..boxes.len() {
-        for (idx, lens) in boxes[boxn].iter().enumerate() {
-            score += (boxn+1)*(idx+1)*lens.focal.parse::<usize>().unwrap(); 
+    fn entry_pos<Q>(&self, key: &Q) -> (usize, Option<usize>)
+    where
+        K: Borrow<Q>,
+        Q: AsRef<[u8]> + Eq + ?Sized,
+    {
+        let boxn = hash(key.as_ref()) as usize;
+        let pos = self.boxes[boxn].iter().position(|(k, _)| k.borrow() == key);
+        (boxn, pos)
+    }
+
+    /// Replaces the value of an existing key in place, otherwise appends to the box.
+    fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry_pos(&key) {
+            (boxn, Some(pos)) => Some(std::mem::replace(&mut self.boxes[boxn][pos].1, value)),
+            (boxn, None) => {
+                self.boxes[boxn].push((key, value));
+                None
+            }
         }
     }
-    score
+
+    /// Removes the key, the entries behind it move forward.
+    fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: AsRef<[u8]> + Eq + ?Sized,
+    {
+        let (boxn, pos) = self.entry_pos(key);
+        Some(self.boxes[boxn].remove(pos?).1)
+    }
+
+    fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: AsRef<[u8]> + Eq + ?Sized,
+    {
+        let (boxn, pos) = self.entry_pos(key);
+        Some(&self.boxes[boxn][pos?].1)
+    }
+
+    /// Entries of one box, front to back.
+    fn box_entries(&self, boxn: u8) -> impl Iterator<Item = (&K, &V)> {
+        self.boxes[boxn as usize].iter().map(|(k, v)| (k, v))
+    }
+
+    /// All entries, box by box.
+    #[allow(unused)]
+    fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.boxes.iter().flatten().map(|(k, v)| (k, v))
+    }
+
+    /// One box in the puzzle's notation, e.g. `Box 3: [ot 7] [ab 5]`.
+    fn format_box(&self, boxn: u8) -> String
+    where
+        K: Display,
+        V: Display,
+    {
+        let mut out = format!("Box {boxn}:");
+        for (k, v) in self.box_entries(boxn) {
+            write!(out, " [{k} {v}]").unwrap();
+        }
+        out
+    }
+
+    /// Sum of box number times slot times focal length, both counted from 1.
+    fn focusing_power(&self) -> usize
+    where
+        V: Copy + Into<usize>,
+    {
+        let mut power = 0;
+        for (boxn, entries) in self.boxes.iter().enumerate() {
+            for (slot, (_, focal)) in entries.iter().enumerate() {
+                power += (boxn + 1) * (slot + 1) * (*focal).into();
+            }
+        }
+        power
+    }
+}
+
+impl<K, V, Q> Index<&Q> for HolidayMap<K, V>
+where
+    K: AsRef<[u8]> + Eq + Borrow<Q>,
+    Q: AsRef<[u8]> + Eq + ?Sized,
+{
+    type Output = V;
+
+    fn index(&self, key: &Q) -> &V {
+        self.get(key).expect("key not in map")
+    }
+}
+
+impl<K, V> IntoIterator for HolidayMap<K, V> {
+    type Item = (K, V);
+    type IntoIter = std::iter::Flatten<std::vec::IntoIter<Vec<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.boxes.into_iter().flatten()
+    }
 }
 
+impl<'a, K, V> IntoIterator for &'a HolidayMap<K, V> {
+    type Item = &'a (K, V);
+    type IntoIter = std::iter::Flatten<std::slice::Iter<'a, Vec<(K, V)>>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.boxes.iter().flatten()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op<'a> {
+    Insert(&'a str, u8),
+    Remove(&'a str),
+}
+
+/// A step of the initialization sequence that is not `label=1..9` or `label-`.
+#[derive(Debug, PartialEq, Eq)]
+struct BadStep<'a> {
+    idx: usize,
+    step: &'a str,
+}
+
+fn parse_steps(input: &str) -> Result<Vec<Op<'_>>, BadStep<'_>> {
+    input
+        .trim()
+        .split(',')
+        .enumerate()
+        .map(|(idx, step)| {
+            let op = if let Some(label) = step.strip_suffix('-') {
+                Some(Op::Remove(label))
+            } else if let Some((label, focal)) = step.split_once('=') {
+                match focal.parse() {
+                    Ok(focal @ 1..=9) => Some(Op::Insert(label, focal)),
+                    _ => None,
+                }
+            } else {
+                None
+            };
+            op.filter(|op| {
+                let (Op::Insert(label, _) | Op::Remove(label)) = op;
+                !label.is_empty() && label.bytes().all(|b| b.is_ascii_alphabetic())
+            })
+            .ok_or(BadStep { idx, step })
+        })
+        .collect()
+}
+
+impl<'a> Op<'a> {
+    /// Performs the step, returns the number of the box it went to.
+    fn apply(self, map: &mut HolidayMap<&'a str, u8>) -> u8 {
+        match self {
+            Op::Insert(label, focal) => {
+                map.insert(label, focal);
+                hash(label.as_bytes())
+            }
+            Op::Remove(label) => {
+                map.remove(label);
+                hash(label.as_bytes())
+            }
+        }
+    }
+}
+
+/// The box each step went to, as it looks after the step.
+/// A removal of a missing label still reports its (unchanged) box.
+fn trace(ops: Vec<Op<'_>>) -> impl Iterator<Item = String> + '_ {
+    let mut map = HolidayMap::new();
+    ops.into_iter().map(move |op| {
+        let boxn = op.apply(&mut map);
+        map.format_box(boxn)
+    })
+}
+
+fn part2(input: &str) -> Result<usize, BadStep<'_>> {
+    let mut map = HolidayMap::new();
+    for op in parse_steps(input)? {
+        op.apply(&mut map);
+    }
+    Ok(map.focusing_power())
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    const EXAMPLE: &str = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(hash(b"HASH"), 52);
+        assert_eq!(part1(EXAMPLE), 1320);
+    }
+
     #[test]
     fn test_part2() {
-        let input = "rn=1,cm-,qp=3,cm=2,qp-,pc=4,ot=9,ab=5,pc-,pc=6,ot=7";
-        assert_eq!(part2(input), 145);
+        assert_eq!(part2(EXAMPLE), Ok(145));
+    }
+
+    #[test]
+    fn test_trace() {
+        let lines: Vec<_> = trace(parse_steps(EXAMPLE).unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "Box 0: [rn 1]",
+                "Box 0: [rn 1]",
+                "Box 1: [qp 3]",
+                "Box 0: [rn 1] [cm 2]",
+                "Box 1:",
+                "Box 3: [pc 4]",
+                "Box 3: [pc 4] [ot 9]",
+                "Box 3: [pc 4] [ot 9] [ab 5]",
+                "Box 3: [ot 9] [ab 5]",
+                "Box 3: [ot 9] [ab 5] [pc 6]",
+                "Box 3: [ot 7] [ab 5] [pc 6]",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_holiday_map() {
+        let mut map = HolidayMap::new();
+        assert_eq!(map.insert(String::from("rn"), 1u8), None);
+        assert_eq!(map.insert(String::from("cm"), 2), None);
+        assert_eq!(map.insert(String::from("rn"), 5), Some(1));
+        assert_eq!(map["rn"], 5);
+        assert_eq!(map.get("qp"), None);
+        // rn and cm share box 0, replacing rn kept it in front
+        let box0: Vec<_> = map.box_entries(0).map(|(k, &v)| (k.as_str(), v)).collect();
+        assert_eq!(box0, vec![("rn", 5), ("cm", 2)]);
+        assert_eq!(map.focusing_power(), 5 + 2 * 2);
+
+        assert_eq!(map.remove("rn"), Some(5));
+        assert_eq!(map.remove("rn"), None);
+        assert_eq!(map.iter().count(), 1);
+        let rest: Vec<_> = map.into_iter().collect();
+        assert_eq!(rest, vec![(String::from("cm"), 2)]);
+    }
+
+    #[test]
+    fn test_bad_steps() {
+        assert_eq!(parse_steps("ab=5,cd-\n").unwrap().len(), 2);
+        for (input, step) in [
+            ("ab=5,cd", "cd"),
+            ("ab=0", "ab=0"),
+            ("ab=12", "ab=12"),
+            ("=3", "=3"),
+            ("ab-3", "ab-3"),
+            ("a-b-", "a-b-"),
+            ("ab=5,,cd-", ""),
+        ] {
+            assert_eq!(parse_steps(input).unwrap_err().step, step, "{input}");
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,7 +1,25 @@
 fn main() {
     let input = include_str!("../input");
-    dbg!(part1(input));
-    dbg!(part2(input));
+    let args: Vec<String> = std::env::args().collect();
+    if args.iter().any(|arg| arg == "--render") {
+        let style = if args.iter().any(|arg| arg == "--plain") {
+            Style::Plain
+        } else {
+            Style::Ansi
+        };
+        let map = parse(input).unwrap();
+        let entry = if args.iter().any(|arg| arg == "--best") {
+            SplitterGraph::new(&map)
+                .best_entry()
+                .map(|(entry, _)| entry)
+        } else {
+            None
+        };
+        let (pos, dir) = entry.unwrap_or(([0, 0], Right));
+        print!("{}", render(&map, &map.energize(pos, dir), entry, style));
+    }
+    dbg!(part1(input).unwrap());
+    dbg!(part2(input).unwrap());
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -12,114 +30,466 @@ enum Dir {
     Down,
 }
 
-impl Dir {
-    fn step(&self, pos: [i64; 2]) -> [i64; 2] {
-        match *self {
-            Left => [pos[0] - 1, pos[1]],
-            Right => [pos[0] + 1, pos[1]],
-            Up => [pos[0], pos[1] - 1],
-            Down => [pos[0], pos[1] + 1],
+use std::collections::HashMap;
+use Dir::*;
+
+#[derive(Debug, PartialEq, Eq)]
+enum MapError {
+    UnknownTile { pos: [usize; 2], tile: u8 },
+    Ragged { row: usize },
+    Empty,
+}
+
+struct Contraption {
+    width: usize,
+    height: usize,
+    tiles: Vec<u8>,
+}
+
+fn parse(input: &str) -> Result<Contraption, MapError> {
+    let rows: Vec<&[u8]> = input.lines().map(|line| line.trim().as_bytes()).collect();
+    let width = rows.first().map_or(0, |row| row.len());
+    if width == 0 {
+        return Err(MapError::Empty);
+    }
+    let mut tiles = Vec::with_capacity(width * rows.len());
+    for (y, row) in rows.iter().enumerate() {
+        if row.len() != width {
+            return Err(MapError::Ragged { row: y });
+        }
+        if let Some(x) = row.iter().position(|t| !b"./\\-|".contains(t)) {
+            return Err(MapError::UnknownTile {
+                pos: [x, y],
+                tile: row[x],
+            });
         }
+        tiles.extend_from_slice(row);
     }
+    Ok(Contraption {
+        width,
+        height: rows.len(),
+        tiles,
+    })
 }
 
-use std::{collections::HashSet, iter::repeat};
-use Dir::*;
+/// Every `(tile, direction)` a beam passed, one bit each.
+struct Beams {
+    width: usize,
+    height: usize,
+    bits: Vec<u64>,
+}
+
+impl Beams {
+    fn new(width: usize, height: usize) -> Self {
+        Beams {
+            width,
+            height,
+            bits: vec![0; (width * height * 4).div_ceil(64)],
+        }
+    }
 
-fn part1(input: &str) -> usize {
-    let map: Vec<Vec<u8>> = input.lines().map(|line| line.trim().bytes().collect()).collect();
+    /// Returns false if the beam was already there.
+    fn insert(&mut self, idx: usize, dir: Dir) -> bool {
+        let bit = idx * 4 + dir as usize;
+        let word = &mut self.bits[bit / 64];
+        let new = *word & (1 << (bit % 64)) == 0;
+        *word |= 1 << (bit % 64);
+        new
+    }
+
+    /// Directions of the beams on a tile, as bits indexed by `Dir`.
+    fn dirs(&self, idx: usize) -> u8 {
+        let bit = idx * 4;
+        (self.bits[bit / 64] >> (bit % 64)) as u8 & 0xf
+    }
 
-    let start_pos = [0, 0];
-    let start_dir = Right;
-    let mut seen = HashSet::new();
-    energize(start_pos, start_dir, &map, &mut seen);
-    
-    let seen_pos: HashSet<_> = seen.iter().map(|(pos,_dir)| *pos).collect();
+    #[allow(unused)]
+    fn mask(&self) -> Vec<Vec<bool>> {
+        (0..self.height)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| self.dirs(y * self.width + x) != 0)
+                    .collect()
+            })
+            .collect()
+    }
 
-    // print_map(&map, &seen);
-    seen_pos.len()
+    fn energized_cnt(&self) -> usize {
+        (0..self.width * self.height)
+            .filter(|&idx| self.dirs(idx) != 0)
+            .count()
+    }
 }
 
-fn part2(input: &str) -> usize {
-    let map: Vec<Vec<u8>> = input.lines().map(|line| line.trim().bytes().collect()).collect();
+impl Contraption {
+    fn step(&self, pos: [usize; 2], dir: Dir) -> Option<[usize; 2]> {
+        let [x, y] = pos;
+        match dir {
+            Left => Some([x.checked_sub(1)?, y]),
+            Right => Some([x + 1, y]).filter(|_| x + 1 < self.width),
+            Up => Some([x, y.checked_sub(1)?]),
+            Down => Some([x, y + 1]).filter(|_| y + 1 < self.height),
+        }
+    }
 
-    let left = repeat(Right).zip(repeat(0i64).zip(0..map.len() as i64));
-    let right = repeat(Left).zip(repeat(map[0].len() as i64 - 1).zip(0..map.len() as i64));
-    let down = repeat(Down).zip((0..map[0].len() as i64).zip(repeat(0)));
-    let up = repeat(Up).zip((0..map.len() as i64).zip(repeat(map.len() as i64-1)));
+    /// Directions a beam leaves a tile in when entering it going `dir`.
+    fn turn(tile: u8, dir: Dir) -> &'static [Dir] {
+        match (tile, dir) {
+            (b'/', Right) | (b'\\', Left) => &[Up],
+            (b'/', Down) | (b'\\', Up) => &[Left],
+            (b'/', Up) | (b'\\', Down) => &[Right],
+            (b'/', Left) | (b'\\', Right) => &[Down],
+            (b'-', Up | Down) => &[Left, Right],
+            (b'|', Left | Right) => &[Up, Down],
+            (_, Left) => &[Left],
+            (_, Right) => &[Right],
+            (_, Up) => &[Up],
+            (_, Down) => &[Down],
+        }
+    }
 
-    left.chain(right).chain(up).chain(down).map(|(dir, pos)|{
-        let mut seen = HashSet::new();
-        energize([pos.0, pos.1], dir, &map, &mut seen);
-        let seen_pos: HashSet<_> = seen.iter().map(|(pos,_dir)| *pos).collect();
-        seen_pos.len()
-    }).max().unwrap()
-}
+    /// Follows all beams from one entering `pos` going `dir`.
+    fn energize(&self, pos: [usize; 2], dir: Dir) -> Beams {
+        let mut beams = Beams::new(self.width, self.height);
+        let mut queue = vec![(pos, dir)];
+        while let Some((pos, dir)) = queue.pop() {
+            let idx = pos[1] * self.width + pos[0];
+            if !beams.insert(idx, dir) {
+                // been here before - done
+                continue;
+            }
+            for &next_dir in Self::turn(self.tiles[idx], dir) {
+                if let Some(next) = self.step(pos, next_dir) {
+                    queue.push((next, next_dir));
+                }
+            }
+        }
+        beams
+    }
 
-#[allow(unused)]
-fn print_map(map: &Vec<Vec<u8>>, seen: &HashSet<([i64; 2], Dir)>) {
-    for (rowidx, row) in map.iter().enumerate() {
-        for (colidx, tile) in row.iter().enumerate() {
-            if [Up,Down,Left,Right].iter().any(|dir|
-                seen.contains(&([colidx as i64, rowidx as i64], *dir))) {
-                    print!("#");
-            } else {
-                print!("{}", char::from(*tile));
+    /// Follows one beam until it leaves the map or gets split, collecting the
+    /// tiles it passes. Returns the index of the splitter that split it.
+    fn trace(&self, mut pos: [usize; 2], mut dir: Dir, tiles: &mut Vec<usize>) -> Option<usize> {
+        // without splitters a beam can only circle between mirrors, at most
+        // once through every (tile, direction)
+        for _ in 0..self.tiles.len() * 4 {
+            let idx = pos[1] * self.width + pos[0];
+            tiles.push(idx);
+            match Self::turn(self.tiles[idx], dir) {
+                [next_dir] => {
+                    dir = *next_dir;
+                    pos = self.step(pos, dir)?;
+                }
+                _ => return Some(idx),
             }
+        }
+        None
+    }
+
+    /// Every tile on the edge with the direction pointing into the contraption.
+    fn entry_points(&self) -> Vec<([usize; 2], Dir)> {
+        let (w, h) = (self.width, self.height);
+        let mut entries = vec![];
+        entries.extend((0..h).map(|y| ([0, y], Right)));
+        entries.extend((0..h).map(|y| ([w - 1, y], Left)));
+        entries.extend((0..w).map(|x| ([x, 0], Down)));
+        entries.extend((0..w).map(|x| ([x, h - 1], Up)));
+        entries
+    }
+}
+
+/// Set of tile indices.
+#[derive(Clone)]
+struct TileSet(Vec<u64>);
 
+impl TileSet {
+    fn new(tile_cnt: usize) -> Self {
+        TileSet(vec![0; tile_cnt.div_ceil(64)])
+    }
+
+    fn insert(&mut self, idx: usize) {
+        self.0[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn union(&mut self, other: &TileSet) {
+        for (word, other) in self.0.iter_mut().zip(&other.0) {
+            *word |= other;
         }
-        println!();
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
     }
 }
 
-fn energize(pos: [i64; 2], dir: Dir, map: &Vec<Vec<u8>>, seen: &mut HashSet<([i64; 2], Dir)>) {
-    if !(0..map.len() as i64 ).contains(&pos[1]) || !(0..map[0].len() as i64).contains(&pos[0]) {
-        // off the map - done
-        return;
+/// Beams cut into segments that end where they hit the flat side of a splitter.
+/// What a split beam lights up is collected per strongly connected group of
+/// splitters, following the groups its beams run into.
+/// Groups more than one beam runs into keep what they light up as a tile set,
+/// so it is only collected once. That is a bit per tile for each such group.
+struct SplitterGraph<'a> {
+    map: &'a Contraption,
+    /// splitter tile index -> node
+    node_of: HashMap<usize, usize>,
+    /// node -> strongly connected component
+    comp_of: Vec<usize>,
+    /// node -> tiles on the beams split there
+    segments: Vec<Vec<usize>>,
+    /// component -> nodes
+    comps: Vec<Vec<usize>>,
+    /// component -> other components its beams run into, once per beam
+    next: Vec<Vec<usize>>,
+    /// component -> tiles lit by beams split in it, if more than one beam runs into it
+    reach: Vec<Option<TileSet>>,
+}
+
+impl<'a> SplitterGraph<'a> {
+    fn new(map: &'a Contraption) -> Self {
+        let splitters: Vec<usize> = (0..map.tiles.len())
+            .filter(|&idx| matches!(map.tiles[idx], b'-' | b'|'))
+            .collect();
+        let node_of: HashMap<usize, usize> = splitters
+            .iter()
+            .enumerate()
+            .map(|(node, &idx)| (idx, node))
+            .collect();
+
+        let mut segments = vec![];
+        let mut edges = vec![];
+        for &idx in &splitters {
+            let pos = [idx % map.width, idx / map.width];
+            let split_dirs = match map.tiles[idx] {
+                b'-' => [Left, Right],
+                _ => [Up, Down],
+            };
+            let mut segment = vec![idx];
+            let mut next_nodes = vec![];
+            for dir in split_dirs {
+                if let Some(next) = map.step(pos, dir) {
+                    if let Some(split) = map.trace(next, dir, &mut segment) {
+                        next_nodes.push(node_of[&split]);
+                    }
+                }
+            }
+            segments.push(segment);
+            edges.push(next_nodes);
+        }
+
+        let comps = sccs(&edges);
+        let mut comp_of = vec![0; splitters.len()];
+        for (comp, nodes) in comps.iter().enumerate() {
+            nodes.iter().for_each(|&node| comp_of[node] = comp);
+        }
+        let mut next = vec![vec![]; comps.len()];
+        let mut incoming = vec![0; comps.len()];
+        for (comp, nodes) in comps.iter().enumerate() {
+            for &node in nodes {
+                for &to in edges[node].iter().map(|&to| &comp_of[to]) {
+                    if to != comp {
+                        next[comp].push(to);
+                        incoming[to] += 1;
+                    }
+                }
+            }
+        }
+
+        let mut graph = SplitterGraph {
+            map,
+            node_of,
+            comp_of,
+            segments,
+            reach: vec![None; comps.len()],
+            comps,
+            next,
+        };
+        // sinks come first, so everything downstream is kept already
+        for comp in (0..graph.comps.len()).filter(|&comp| incoming[comp] > 1) {
+            let mut reach = TileSet::new(map.tiles.len());
+            graph.light(comp, &mut reach);
+            graph.reach[comp] = Some(reach);
+        }
+        graph
     }
-    if seen.insert((pos,dir)) == false {
-        // been here before - done
-        return;
+
+    /// Adds the tiles lit by beams split in `comp` to `tiles`.
+    fn light(&self, comp: usize, tiles: &mut TileSet) {
+        let mut todo = vec![comp];
+        while let Some(comp) = todo.pop() {
+            match &self.reach[comp] {
+                Some(reach) => tiles.union(reach),
+                None => {
+                    for &node in &self.comps[comp] {
+                        self.segments[node]
+                            .iter()
+                            .for_each(|&idx| tiles.insert(idx));
+                    }
+                    todo.extend(&self.next[comp]);
+                }
+            }
+        }
     }
-    // print_map(map, seen);
-    // println!();
 
-    match (map[pos[1] as usize][pos[0] as usize], dir) {
-        (b'.', _) => energize(dir.step(pos), dir, map, seen),
-        // mirror /
-        (b'/', Right) => energize(Up.step(pos), Up, map, seen),
-        (b'/', Down) => energize(Left.step(pos), Left, map, seen),
-        (b'/', Up) => energize(Right.step(pos), Right, map, seen),
-        (b'/', Left) => energize(Down.step(pos), Down, map, seen),
-        // mirror \
-        (b'\\', Right) => energize(Down.step(pos), Down, map, seen),
-        (b'\\', Up) => energize(Left.step(pos), Left, map, seen),
-        (b'\\', Left) => energize(Up.step(pos), Up, map, seen),
-        (b'\\', Down) => energize(Right.step(pos), Right, map, seen),
-        // splitter -
-        (b'-', Left | Right) => energize(dir.step(pos), dir, map, seen),
-        (b'-', Up|Down) => {
-            energize(Left.step(pos), Left, map, seen);
-            energize(Right.step(pos), Right, map, seen);
+    fn energized_cnt(&self, pos: [usize; 2], dir: Dir) -> usize {
+        let mut segment = vec![];
+        let mut tiles = TileSet::new(self.map.tiles.len());
+        if let Some(split) = self.map.trace(pos, dir, &mut segment) {
+            self.light(self.comp_of[self.node_of[&split]], &mut tiles);
         }
-        // splitter |
-        (b'|', Up | Down) => energize(dir.step(pos), dir, map, seen),
-        (b'|', Left | Right) => {
-            energize(Up.step(pos), Up, map, seen);
-            energize(Down.step(pos), Down, map, seen);
+        segment.iter().for_each(|&idx| tiles.insert(idx));
+        tiles.len()
+    }
+
+    /// The entry point lighting up the most tiles, with their count.
+    fn best_entry(&self) -> Option<(([usize; 2], Dir), usize)> {
+        self.map
+            .entry_points()
+            .into_iter()
+            .map(|(pos, dir)| ((pos, dir), self.energized_cnt(pos, dir)))
+            .max_by_key(|&(_, cnt)| cnt)
+    }
+}
+
+/// Tarjan's algorithm with an explicit stack. Components come out sinks first.
+fn sccs(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNSEEN: usize = usize::MAX;
+    let mut index = vec![UNSEEN; edges.len()];
+    let mut low = vec![0; edges.len()];
+    let mut on_stack = vec![false; edges.len()];
+    let mut stack = vec![];
+    let mut comps = vec![];
+    let mut next_index = 0;
+    for root in 0..edges.len() {
+        if index[root] != UNSEEN {
+            continue;
+        }
+        // (node, next edge to look at)
+        let mut calls = vec![(root, 0)];
+        index[root] = next_index;
+        low[root] = next_index;
+        next_index += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        while let Some(&(v, edge)) = calls.last() {
+            if let Some(&w) = edges[v].get(edge) {
+                calls.last_mut().unwrap().1 += 1;
+                if index[w] == UNSEEN {
+                    index[w] = next_index;
+                    low[w] = next_index;
+                    next_index += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    calls.push((w, 0));
+                } else if on_stack[w] {
+                    low[v] = low[v].min(index[w]);
+                }
+                continue;
+            }
+            calls.pop();
+            if let Some(&(parent, _)) = calls.last() {
+                low[parent] = low[parent].min(low[v]);
+            }
+            if low[v] == index[v] {
+                let mut comp = vec![];
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack[w] = false;
+                    comp.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                comps.push(comp);
+            }
         }
-        _ => todo!(),
     }
+    comps
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Plain,
+    Ansi,
+}
+
+fn arrow(dir: Dir) -> char {
+    match dir {
+        Left => '<',
+        Right => '>',
+        Up => '^',
+        Down => 'v',
+    }
+}
+
+/// The contraption with the beams drawn onto its empty tiles like in the puzzle:
+/// an arrow for a single direction, otherwise the number of directions.
+/// With an entry point there is a margin around the map showing where the beam comes in.
+fn render(
+    map: &Contraption,
+    beams: &Beams,
+    entry: Option<([usize; 2], Dir)>,
+    style: Style,
+) -> String {
+    let margin = entry.is_some() as usize;
+    // entry position in margin coordinates, one step back against the beam
+    let marker = entry.map(|([x, y], dir)| {
+        let pos = match dir {
+            Left => [x + 2, y + 1],
+            Right => [x, y + 1],
+            Up => [x + 1, y + 2],
+            Down => [x + 1, y],
+        };
+        (pos, dir)
+    });
+    let mut out = String::new();
+    for y in 0..map.height + 2 * margin {
+        for x in 0..map.width + 2 * margin {
+            let inside = (margin..map.width + margin).contains(&x)
+                && (margin..map.height + margin).contains(&y);
+            let (glyph, color) = if inside {
+                let idx = (y - margin) * map.width + x - margin;
+                let dirs = beams.dirs(idx);
+                let glyph = match (map.tiles[idx], dirs.count_ones()) {
+                    (b'.', 0) => '.',
+                    (b'.', 1) => arrow([Left, Right, Up, Down][dirs.trailing_zeros() as usize]),
+                    (b'.', n) => char::from_digit(n, 10).unwrap(),
+                    (tile, _) => char::from(tile),
+                };
+                (glyph, if dirs != 0 { "\x1b[1;33m" } else { "\x1b[2m" })
+            } else {
+                match marker {
+                    Some((pos, dir)) if pos == [x, y] => (arrow(dir), "\x1b[1;31m"),
+                    _ => (' ', ""),
+                }
+            };
+            if style == Style::Ansi && !color.is_empty() {
+                out.push_str(color);
+                out.push(glyph);
+                out.push_str("\x1b[0m");
+            } else {
+                out.push(glyph);
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn part1(input: &str) -> Result<usize, MapError> {
+    let map = parse(input)?;
+    Ok(map.energize([0, 0], Right).energized_cnt())
+}
+
+fn part2(input: &str) -> Result<usize, MapError> {
+    let map = parse(input)?;
+    let best = SplitterGraph::new(&map).best_entry();
+    Ok(best.map_or(0, |(_, cnt)| cnt))
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_part1() {
-        let input = r".|...\....
+    const EXAMPLE: &str = r".|...\....
         |.-.\.....
         .....|-...
         ........|.
@@ -129,6 +499,106 @@ mod tests {
         .-.-/..|..
         .|....-|.\
         ..//.|....";
-        assert_eq!(46, part1(input));    
+
+    #[test]
+    fn test_part1() {
+        assert_eq!(Ok(46), part1(EXAMPLE));
+    }
+
+    #[test]
+    fn test_part2() {
+        assert_eq!(Ok(51), part2(EXAMPLE));
+    }
+
+    #[test]
+    fn test_best_entry() {
+        let map = parse(EXAMPLE).unwrap();
+        let graph = SplitterGraph::new(&map);
+        assert_eq!(graph.best_entry(), Some((([3, 0], Down), 51)));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_graph_matches_simulation() {
+        // splitters feeding each other round a square, and beams crossing it
+        let square = r"
+            ..|....
+            ./-\...
+            .|.|.-.
+            .\-/.\.
+            ...-.|.
+            ..\|-/.";
+        for input in [EXAMPLE, square.trim(), include_str!("../input")] {
+            let map = parse(input).unwrap();
+            let graph = SplitterGraph::new(&map);
+            for (pos, dir) in map.entry_points() {
+                assert_eq!(
+                    graph.energized_cnt(pos, dir),
+                    map.energize(pos, dir).energized_cnt(),
+                    "{pos:?} {dir:?}\n{input}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        let map = parse(EXAMPLE).unwrap();
+        let beams = map.energize([0, 0], Right);
+        assert_eq!(
+            render(&map, &beams, None, Style::Plain),
+            r">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v..
+"
+        );
+        let ansi = render(&map, &beams, None, Style::Ansi);
+        assert!(ansi.starts_with("\x1b[1;33m>\x1b[0m"));
+        assert_eq!(ansi.matches("\x1b[1;33m").count(), 46);
+
+        let beams = map.energize([3, 0], Down);
+        let with_entry = render(&map, &beams, Some(([3, 0], Down)), Style::Plain);
+        let rows: Vec<&str> = with_entry.lines().collect();
+        assert_eq!(rows.len(), 12);
+        assert_eq!(rows[0], "    v       ");
+        assert_eq!(rows[1], r" .|<2<\.... ");
+    }
+
+    #[test]
+    fn test_mask() {
+        let mask = parse(EXAMPLE).unwrap().energize([0, 0], Right).mask();
+        let rows: Vec<String> = mask
+            .iter()
+            .map(|row| row.iter().map(|&e| if e { '#' } else { '.' }).collect())
+            .collect();
+        assert_eq!(rows[0], "######....");
+        assert_eq!(rows[9], ".#...#.#..");
+    }
+
+    #[test]
+    fn test_long_corridor() {
+        // deep enough to overflow the stack when following the beam recursively
+        let input = ".".repeat(1_000_000);
+        assert_eq!(part1(&input), Ok(1_000_000));
+    }
+
+    #[test]
+    fn test_bad_map() {
+        assert_eq!(
+            part1("..\n.x\n"),
+            Err(MapError::UnknownTile {
+                pos: [1, 1],
+                tile: b'x'
+            })
+        );
+        assert_eq!(part1("..\n.\n"), Err(MapError::Ragged { row: 1 }));
+        assert_eq!(part1(""), Err(MapError::Empty));
+        assert_eq!(part2("\n"), Err(MapError::Empty));
+    }
+}
@@ -1,9 +1,10 @@
+use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap};
 
 fn main() {
     let input = include_str!("../input");
-    dbg!(part1(input));
-    dbg!(part2(input));
+    dbg!(part1(input).unwrap());
+    dbg!(part2(input).unwrap());
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
@@ -41,6 +42,14 @@ impl Dir {
             West => South,
         }
     }
+    fn back(&self) -> Self {
+        match self {
+            North => South,
+            South => North,
+            East => West,
+            West => East,
+        }
+    }
 }
 
 #[derive(PartialEq, Eq, Hash, Clone)]
@@ -54,17 +63,13 @@ struct State {
 struct Item {
     state: State,
     cost: i32,
-}
-
-impl Item {
-    fn est_cost(&self) -> i32 {
-        self.cost - self.state.pos[0] - self.state.pos[1]
-    }
+    /// cost plus a lower bound of the cost left to the goal
+    est_cost: i32,
 }
 
 impl Ord for Item {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.est_cost().cmp(&other.est_cost()).reverse()
+        self.est_cost.cmp(&other.est_cost).reverse()
     }
 }
 
@@ -74,97 +79,178 @@ impl PartialOrd for Item {
     }
 }
 
-fn part1(input: &str) -> i32 {
-    run_cart(input, 0, 3)
+type Map = Vec<Vec<u8>>;
+
+fn parse(input: &str) -> Map {
+    input
+        .lines()
+        .map(|line| line.trim().bytes().map(|ch| ch - b'0').collect())
+        .collect()
 }
 
-fn part2(input: &str) -> i32 {
-    run_cart(input, 4, 10)
+/// No path to the goal under the router's rules.
+#[derive(Debug, PartialEq, Eq)]
+struct Unreachable;
+
+/// Where a crucible goes and how it may move on the way.
+#[derive(Debug)]
+struct Router {
+    start: [i32; 2],
+    goal: [i32; 2],
+    /// directions the first step may go in
+    start_dirs: Vec<Dir>,
+    /// blocks to go straight before turning, counting the first step
+    min_straight: u8,
+    max_straight: u8,
+    u_turns: bool,
 }
 
-fn run_cart(input: &str, min_straight: u8, max_straight: u8) -> i32 {
-    // A* algorithm woooooo
-    
-    let map: Vec<_> = input
-        .lines()
-        .map(|line| line.trim().bytes().map(|ch| ch - b'0').collect::<Vec<_>>())
-        .collect();
-
-    let in_map = |pos: [i32; 2]| {
-        (0..map.len() as i32).contains(&pos[1]) && (0..map[0].len() as i32).contains(&pos[0])
-    };
-
-    let mut work = BinaryHeap::new();
-    let start = Item {
-        cost: 0,
-        state: State {
-            pos: [0, 0],
-            last_dir: South,
-            straight_steps: 0,
-        },
-    };
-    work.push(start);
-
-    let target = [map[0].len() as i32 - 1, map.len() as i32 - 1];
-    let mut best = HashMap::new();
-    while let Some(Item { cost, state }) = work.pop() {
-        if state.pos == target {
-            return cost;
+impl Router {
+    /// From the top-left to the bottom-right corner, starting east or south.
+    fn corner_to_corner(map: &Map, min_straight: u8, max_straight: u8) -> Self {
+        Router {
+            start: [0, 0],
+            goal: [map[0].len() as i32 - 1, map.len() as i32 - 1],
+            start_dirs: vec![East, South],
+            min_straight,
+            max_straight,
+            u_turns: false,
         }
-        if let Some(prev) = best.get(&state) {
-            if *prev <= cost {
+    }
+
+    /// Least heat loss from start to goal, the start block does not count.
+    fn route(&self, map: &Map) -> Result<i32, Unreachable> {
+        if !in_map(map, self.goal) {
+            return Err(Unreachable);
+        }
+        let remaining = self.remaining_cost(map);
+        self.search(map, |pos| remaining[pos[1] as usize][pos[0] as usize])
+    }
+
+    /// Same as `route` but without a heuristic.
+    #[cfg(test)]
+    fn dijkstra(&self, map: &Map) -> Result<i32, Unreachable> {
+        self.search(map, |_| 0)
+    }
+
+    /// Least cost from every block to the goal when moving freely, which never
+    /// overestimates the cost under the straight run rules.
+    /// Blocks that can't reach the goal at all get `i32::MAX`.
+    fn remaining_cost(&self, map: &Map) -> Vec<Vec<i32>> {
+        let mut remaining = vec![vec![i32::MAX; map[0].len()]; map.len()];
+        let mut work = BinaryHeap::new();
+        work.push(Reverse((0, self.goal)));
+        while let Some(Reverse((cost, pos))) = work.pop() {
+            let cell = &mut remaining[pos[1] as usize][pos[0] as usize];
+            if *cell <= cost {
                 continue;
             }
+            *cell = cost;
+            // entering `pos` from a neighbour costs the heat loss of `pos`
+            let cost = cost + map[pos[1] as usize][pos[0] as usize] as i32;
+            for dir in [North, South, East, West] {
+                let prev = dir.step(pos);
+                if in_map(map, prev) {
+                    work.push(Reverse((cost, prev)));
+                }
+            }
         }
-        best.insert(state.clone(), cost);
+        remaining
+    }
+
+    /// A* with `estimate` as a lower bound of the cost left from a block.
+    fn search(&self, map: &Map, estimate: impl Fn([i32; 2]) -> i32) -> Result<i32, Unreachable> {
+        if !in_map(map, self.start) || !in_map(map, self.goal) {
+            return Err(Unreachable);
+        }
+        let est_cost = |cost: i32, pos: [i32; 2]| cost.saturating_add(estimate(pos));
+
+        // A* algorithm woooooo
+        let mut work = BinaryHeap::new();
+        for &dir in &self.start_dirs {
+            work.push(Item {
+                cost: 0,
+                est_cost: est_cost(0, self.start),
+                state: State {
+                    pos: self.start,
+                    last_dir: dir,
+                    straight_steps: 0,
+                },
+            });
+        }
+
+        let mut best = HashMap::new();
+        while let Some(Item { cost, state, .. }) = work.pop() {
+            // the last run has to be long enough too, unless there was no step at all
+            if state.pos == self.goal
+                && (state.straight_steps == 0 || state.straight_steps >= self.min_straight)
+            {
+                return Ok(cost);
+            }
+            if let Some(prev) = best.get(&state) {
+                if *prev <= cost {
+                    continue;
+                }
+            }
+            best.insert(state.clone(), cost);
 
-        // turns first
-        if state.straight_steps >= min_straight {
-            for dir in [state.last_dir.left(), state.last_dir.right()] {
+            let mut dirs = vec![];
+            // turns first, but not before the first step
+            if state.straight_steps > 0 && state.straight_steps >= self.min_straight {
+                dirs.extend([state.last_dir.left(), state.last_dir.right()]);
+                if self.u_turns {
+                    dirs.push(state.last_dir.back());
+                }
+            }
+            if state.straight_steps < self.max_straight {
+                dirs.push(state.last_dir);
+            }
+
+            for dir in dirs {
                 let pos = dir.step(state.pos);
-                if !in_map(pos) {
+                if !in_map(map, pos) {
                     continue;
                 }
-                let next = Item {
-                    cost: cost + map[pos[1] as usize][pos[0] as usize] as i32,
+                let cost = cost + map[pos[1] as usize][pos[0] as usize] as i32;
+                let straight_steps = match dir == state.last_dir {
+                    true => state.straight_steps + 1,
+                    false => 1,
+                };
+                work.push(Item {
+                    cost,
+                    est_cost: est_cost(cost, pos),
                     state: State {
                         pos,
                         last_dir: dir,
-                        straight_steps: 1,
+                        straight_steps,
                     },
-                };
-                work.push(next);
+                });
             }
         }
 
-        // straight
-        if state.straight_steps < max_straight {
-            let pos = state.last_dir.step(state.pos);
-            if !in_map(pos) {
-                continue;
-            }
-            let next = Item {
-                cost: cost + map[pos[1] as usize][pos[0] as usize] as i32,
-                state: State {
-                    pos,
-                    last_dir: state.last_dir,
-                    straight_steps: state.straight_steps + 1,
-                },
-            };
-            work.push(next);
-        }
+        Err(Unreachable)
     }
+}
+
+fn in_map(map: &Map, pos: [i32; 2]) -> bool {
+    (0..map.len() as i32).contains(&pos[1]) && (0..map[0].len() as i32).contains(&pos[0])
+}
+
+fn part1(input: &str) -> Result<i32, Unreachable> {
+    let map = parse(input);
+    Router::corner_to_corner(&map, 0, 3).route(&map)
+}
 
-    unreachable!()
+fn part2(input: &str) -> Result<i32, Unreachable> {
+    let map = parse(input);
+    Router::corner_to_corner(&map, 4, 10).route(&map)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test() {
-        let input = "2413432311323
+    const EXAMPLE: &str = "2413432311323
         3215453535623
         3255245654254
         3446585845452
@@ -177,6 +263,133 @@ mod tests {
         1224686865563
         2546548887735
         4322674655533";
-        assert_eq!(102, part1(input));
+
+    #[test]
+    fn test() {
+        assert_eq!(Ok(102), part1(EXAMPLE));
+        assert_eq!(Ok(94), part2(EXAMPLE));
+    }
+
+    #[test]
+    fn test_min_straight_at_goal() {
+        let input = "111111111111
+        999999999991
+        999999999991
+        999999999991
+        999999999991";
+        // turning down right at the corner would end in a run of four
+        assert_eq!(Ok(71), part2(input));
+    }
+
+    #[test]
+    fn test_astar_matches_dijkstra() {
+        // xorshift, so the grids are the same on every run
+        let mut state = 0x9e37_79b9_7f4a_7c15u64;
+        let mut rnd = move |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as usize
+        };
+        for _ in 0..300 {
+            let (w, h) = (rnd(10) + 1, rnd(10) + 1);
+            let map: Map = (0..h)
+                .map(|_| (0..w).map(|_| rnd(9) as u8 + 1).collect())
+                .collect();
+            let min_straight = rnd(5) as u8;
+            let router = Router {
+                start: [rnd(w) as i32, rnd(h) as i32],
+                goal: [rnd(w) as i32, rnd(h) as i32],
+                start_dirs: [North, South, East, West]
+                    .into_iter()
+                    .filter(|_| rnd(2) == 0)
+                    .collect(),
+                min_straight,
+                max_straight: min_straight.max(1) + rnd(6) as u8,
+                u_turns: rnd(2) == 0,
+            };
+            assert_eq!(
+                router.route(&map),
+                router.dijkstra(&map),
+                "{router:?}\n{map:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_astar_matches_dijkstra_examples() {
+        let maps = [
+            parse(EXAMPLE),
+            parse("111111111111\n999999999991\n999999999991\n999999999991"),
+            parse("1111\n9991\n1111\n1999\n1111"),
+            parse("191\n"),
+        ];
+        for map in &maps {
+            let (w, h) = (map[0].len() as i32, map.len() as i32);
+            let corners = [[0, 0], [w - 1, 0], [0, h - 1], [w - 1, h - 1]];
+            let starts = corners.into_iter().chain([[w / 2, h / 2]]);
+            for (start, goal) in starts.flat_map(|start| corners.map(|goal| (start, goal))) {
+                for start_dirs in [vec![East, South], vec![North, South, East, West]] {
+                    for (min_straight, max_straight) in [(0, 3), (1, 3), (2, 2), (4, 10)] {
+                        for u_turns in [false, true] {
+                            let router = Router {
+                                start,
+                                goal,
+                                start_dirs: start_dirs.clone(),
+                                min_straight,
+                                max_straight,
+                                u_turns,
+                            };
+                            assert_eq!(router.route(map), router.dijkstra(map), "{router:?}");
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_router() {
+        let map = parse(
+            "1111
+            9991
+            1111
+            1999
+            1111",
+        );
+        let mut router = Router::corner_to_corner(&map, 1, 3);
+        // the cheapest way is the snake through the ones
+        assert_eq!(router.route(&map), Ok(3 + 2 + 3 + 2 + 3));
+        router.min_straight = 5;
+        assert_eq!(router.route(&map), Err(Unreachable));
+
+        // straight up through the nine, or back along the snake
+        let mut router = Router {
+            start: [3, 4],
+            goal: [3, 0],
+            start_dirs: vec![North],
+            min_straight: 1,
+            max_straight: 10,
+            u_turns: false,
+        };
+        assert_eq!(router.route(&map), Ok(9 + 1 + 1 + 1));
+        router.start_dirs = vec![West];
+        assert_eq!(router.route(&map), Ok(3 + 2 + 3 + 2));
+        router.start = [4, 0];
+        assert_eq!(router.route(&map), Err(Unreachable));
+
+        // heading away from the goal in a corridor, only a u-turn gets back
+        let map = parse("111");
+        let mut router = Router {
+            start: [1, 0],
+            goal: [2, 0],
+            start_dirs: vec![West],
+            min_straight: 1,
+            max_straight: 3,
+            u_turns: false,
+        };
+        assert_eq!(router.route(&map), Err(Unreachable));
+        router.u_turns = true;
+        assert_eq!(router.route(&map), Ok(3));
     }
 }
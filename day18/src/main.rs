@@ -1,14 +1,21 @@
-use std::collections::{BTreeSet, BinaryHeap, HashSet, VecDeque};
+use polygon::{PlanError, Polygon, Pt};
 
 fn main() {
     let input = include_str!("../input");
-    dbg!(part1(input));
-    dbg!(part2(input));
+    let args: Vec<String> = std::env::args().collect();
+    // `--svg 1` or `--svg 2` prints the lagoon of that part
+    if let Some(idx) = args.iter().position(|arg| arg == "--svg") {
+        let mut plan = parse(input).unwrap();
+        if args.get(idx + 1).map(String::as_str) == Some("2") {
+            plan = decode(&plan).unwrap();
+        }
+        print!("{}", render_svg(&plan, 800).unwrap());
+    }
+    dbg!(part1(input).unwrap());
+    dbg!(part2(input).unwrap());
 }
 
-type Pt = [i32; 2];
-
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 enum Dir {
     Up,
     Down,
@@ -17,243 +24,300 @@ enum Dir {
 }
 
 impl Dir {
-    fn step(&self, mut pt: Pt) -> Pt {
+    fn delta(&self, amt: i64) -> Pt {
         match self {
-            Dir::Up => pt[1] -= 1,
-            Dir::Down => pt[1] += 1,
-            Dir::Left => pt[0] -= 1,
-            Dir::Right => pt[0] += 1,
+            Dir::Up => [0, -amt],
+            Dir::Down => [0, amt],
+            Dir::Left => [-amt, 0],
+            Dir::Right => [amt, 0],
         }
-        pt
-    }
-}
-
-fn parse_part1(input: &str) -> Vec<(Dir, i32)> {
-    let mut result = vec![];
-    for line in input.lines() {
-        let mut split = line.split_whitespace();
-        let cmd = split.next().unwrap();
-        let amt = split.next().unwrap().parse::<i32>().unwrap();
-        let _code = split.next().unwrap();
-
-        result.push(match cmd {
-            "L" => (Dir::Left, amt),
-            "R" => (Dir::Right, amt),
-            "U" => (Dir::Up, amt),
-            "D" => (Dir::Down, amt),
-            _ => unimplemented!(),
-        });
     }
-    result
 }
 
-fn part1(input: &str) -> usize {
-    let insts = parse_part1(input);
-    area_flood(&insts)
+/// One line of the dig plan, read as in part 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Step {
+    dir: Dir,
+    amt: i64,
+    /// 0xrrggbb
+    color: u32,
 }
 
-fn area_flood(insts: &[(Dir, i32)]) -> usize {
-    let mut map = HashSet::new();
-    let mut pos = [0, 0];
-
-    for (dir, amt) in insts {
-        for _n in 0..*amt {
-            match dir {
-                Dir::Left => pos[0] -= 1,
-                Dir::Right => pos[0] += 1,
-                Dir::Up => pos[1] -= 1,
-                Dir::Down => pos[1] += 1,
-            }
-            map.insert(pos);
+impl Step {
+    /// Reads a line like `R 6 (#70c710)`.
+    fn parse(line: &str) -> Option<Step> {
+        let mut split = line.split_whitespace();
+        let dir = match split.next()? {
+            "L" => Dir::Left,
+            "R" => Dir::Right,
+            "U" => Dir::Up,
+            "D" => Dir::Down,
+            _ => return None,
+        };
+        let amt = split.next()?.parse::<i64>().ok()?;
+        let code = split.next()?.strip_prefix("(#")?.strip_suffix(')')?;
+        if code.len() != 6 || !code.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+        let color = u32::from_str_radix(code, 16).ok()?;
+        match split.next() {
+            Some(_) => None,
+            None => Some(Step { dir, amt, color }),
         }
     }
 
-    // find extremeties of the map
-    let mut mins = [0, 0];
-    let mut maxs = [0, 0];
-    for pos in map.iter() {
-        mins[0] = mins[0].min(pos[0]);
-        mins[1] = mins[1].min(pos[1]);
-        maxs[0] = maxs[0].max(pos[0]);
-        maxs[1] = maxs[1].max(pos[1]);
+    /// The part 2 reading, with distance and direction taken from the colour code.
+    /// The colour itself stays the same. `None` if the last digit is no direction.
+    fn decoded(&self) -> Option<Step> {
+        let dir = match self.color & 0xf {
+            0 => Dir::Right,
+            1 => Dir::Down,
+            2 => Dir::Left,
+            3 => Dir::Up,
+            _ => return None,
+        };
+        Some(Step {
+            dir,
+            amt: (self.color >> 4) as i64,
+            color: self.color,
+        })
     }
-    let xbounds = mins[0] - 1..=maxs[0] + 1;
-    let ybounds = mins[1] - 1..=maxs[1] + 1;
+}
 
-    let orig_size = map.len();
+/// Why a dig plan can't be dug. Lines are numbered from 0, like the moves.
+#[derive(Debug, PartialEq, Eq)]
+enum DigError {
+    /// the line is not like `R 6 (#70c710)`
+    Malformed {
+        step: usize,
+        line: String,
+    },
+    /// the last digit of the colour code is no direction
+    BadDirection {
+        step: usize,
+        color: u32,
+    },
+    Plan(PlanError),
+}
 
-    // now flood fill to get outer area
-    let mut work = VecDeque::new();
-    work.push_back([mins[0] - 1, mins[1] - 1]);
-    while let Some(pos) = work.pop_front() {
-        if map.contains(&pos) {
-            continue;
-        }
-        if !xbounds.contains(&pos[0]) || !ybounds.contains(&pos[1]) {
-            continue;
-        }
-        map.insert(pos);
-        use Dir::*;
-        for dir in [Up, Down, Left, Right] {
-            work.push_back(dir.step(pos));
-        }
+impl From<PlanError> for DigError {
+    fn from(err: PlanError) -> Self {
+        DigError::Plan(err)
     }
+}
 
-    let outside_area = map.len() - orig_size;
-    xbounds.count() * ybounds.count() - outside_area
+fn parse(input: &str) -> Result<Vec<Step>, DigError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(step, line)| {
+            Step::parse(line).ok_or_else(|| DigError::Malformed {
+                step,
+                line: line.to_string(),
+            })
+        })
+        .collect()
 }
 
-#[derive(PartialEq, Eq)]
-struct YFirst([i32; 2]);
-impl Ord for YFirst {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        self.0[1]
-            .cmp(&other.0[1])
-            .then(self.0[0].cmp(&other.0[0]))
-            .reverse()
-    }
+/// The plan as read in part 2.
+fn decode(plan: &[Step]) -> Result<Vec<Step>, DigError> {
+    plan.iter()
+        .enumerate()
+        .map(|(step, read)| {
+            read.decoded().ok_or(DigError::BadDirection {
+                step,
+                color: read.color,
+            })
+        })
+        .collect()
 }
-impl PartialOrd for YFirst {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        Some(self.cmp(other))
-    }
+
+fn to_polygon(plan: &[Step]) -> Result<Polygon, PlanError> {
+    Polygon::from_moves(plan.iter().map(|step| step.dir.delta(step.amt)))
 }
 
-fn parse_part2(input: &str) -> Vec<(Dir, i32)> {
-    let mut result = vec![];
-    for line in input.lines() {
-        let mut split = line.split_whitespace();
-        let _cmd = split.next().unwrap();
-        let _amt = split.next().unwrap().parse::<i32>().unwrap();
-        let code = split
-            .next()
-            .unwrap()
-            .strip_prefix("(#")
-            .unwrap()
-            .strip_suffix(")")
-            .unwrap();
-        let (amt, cmd) = code.split_at(5);
-        let amt = i32::from_str_radix(amt, 16).unwrap();
-
-        result.push(match cmd {
-            "0" | "R" => (Dir::Right, amt),
-            "1" | "D" => (Dir::Down, amt),
-            "2" | "L" => (Dir::Left, amt),
-            "3" | "U" => (Dir::Up, amt),
-            _ => unimplemented!(),
-        });
+fn lagoon_size(plan: &[Step]) -> Result<i64, PlanError> {
+    let polygon = to_polygon(plan)?;
+    Ok(polygon.interior() + polygon.boundary())
+}
+
+/// The lagoon as an SVG image `width` pixels wide: the interior filled and every
+/// trench segment drawn in its colour, at least 2 pixels thick.
+fn render_svg(plan: &[Step], width: u32) -> Result<String, PlanError> {
+    let polygon = to_polygon(plan)?;
+    let min = |axis: usize| polygon.vertices.iter().map(|v| v[axis]).min().unwrap_or(0);
+    let max = |axis: usize| polygon.vertices.iter().map(|v| v[axis]).max().unwrap_or(0);
+    // trench cubes are centered on the lattice points
+    let (x0, y0) = (min(0) as f64 - 0.5, min(1) as f64 - 0.5);
+    let (w, h) = ((max(0) - min(0) + 1) as f64, (max(1) - min(1) + 1) as f64);
+    let height = (width as f64 * h / w).ceil().max(1.0);
+    let stroke = (2.0 * w / width as f64).max(1.0);
+
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+         viewBox=\"{x0} {y0} {w} {h}\">\n"
+    );
+    let points: Vec<String> = polygon
+        .vertices
+        .iter()
+        .map(|[x, y]| format!("{x},{y}"))
+        .collect();
+    svg += &format!("<polygon points=\"{}\" fill=\"#ccc\"/>\n", points.join(" "));
+    let mut pos = [0, 0];
+    for step in plan {
+        let [dx, dy] = step.dir.delta(step.amt);
+        let next = [pos[0] + dx, pos[1] + dy];
+        svg += &format!(
+            "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"#{:06x}\" \
+             stroke-width=\"{stroke}\" stroke-linecap=\"square\"/>\n",
+            pos[0], pos[1], next[0], next[1], step.color
+        );
+        pos = next;
     }
-    result
+    svg += "</svg>\n";
+    Ok(svg)
 }
 
-fn part2(input: &str) -> i64 {
-    let insts = parse_part2(input);
-    area_smart(&insts)
+fn part1(input: &str) -> Result<i64, DigError> {
+    Ok(lagoon_size(&parse(input)?)?)
 }
 
-fn area_smart(insts: &[(Dir, i32)]) -> i64 {
-    let mut map = BinaryHeap::new();
+fn part2(input: &str) -> Result<i64, DigError> {
+    Ok(lagoon_size(&decode(&parse(input)?)?)?)
+}
 
-    let mut pos = [0, 0];
+/// Rectilinear polygons on the integer grid.
+mod polygon {
+    pub type Pt = [i64; 2];
 
-    for (dir, amt) in insts {
-        let start_pos = pos;
-        match dir {
-            Dir::Right => pos[0] += amt,
-            Dir::Down => pos[1] += amt,
-            Dir::Left => pos[0] -= amt,
-            Dir::Up => pos[1] -= amt,
-        }
-        //println!("{:?} from {:?} to {:?}", dir, start_pos, pos);
-        match dir {
-            Dir::Up | Dir::Down => {
-                map.push(YFirst(start_pos));
-                map.push(YFirst(pos));
-            }
-            _ => (),
-        }
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum PlanError {
+        /// the moves end at `end` instead of back at the start
+        NotClosed { end: Pt },
+        /// moves `a` and `b` meet somewhere other than their shared corner
+        SelfIntersecting { a: usize, b: usize },
+        /// there are no moves, or they all have length zero
+        Empty,
     }
 
-    let mut edges = vec![];
-    let mut last_y = -1;
-    let mut area = 0;
-    while let Some(YFirst([_, new_y])) = map.peek() {
-        let delta_y = new_y - last_y - 1;
-        if delta_y != 0 {
-            let delta_area = delta_y as i64
-                * edges
-                    .chunks(2)
-                    .map(|pair| (pair[1] - pair[0] + 1) as i64)
-                    .sum::<i64>();
-            area += delta_area;
+    /// A simple polygon with horizontal and vertical edges.
+    pub struct Polygon {
+        /// corners in walking order, without repeating the first one at the end
+        pub vertices: Vec<Pt>,
+    }
+
+    /// Closed segment between two points, with the number of the move it came from.
+    struct Segment {
+        from: Pt,
+        to: Pt,
+        step: usize,
+    }
+
+    impl Segment {
+        fn dir(&self) -> Pt {
+            [
+                (self.to[0] - self.from[0]).signum(),
+                (self.to[1] - self.from[1]).signum(),
+            ]
         }
-        last_y = *new_y;
 
-        let mut next_edges: BTreeSet<_> = edges.iter().copied().collect();
-        while let Some(YFirst([x, y])) = map.peek() {
-            if *y != last_y {
-                break;
-            }
-            if next_edges.contains(x) {
-                next_edges.remove(x);
-            } else {
-                next_edges.insert(*x);
-            }
-            map.pop(); // discard
+        /// Axis-aligned segments share a point iff their bounding boxes do.
+        fn touches(&self, other: &Segment) -> bool {
+            (0..2).all(|axis| {
+                let (lo, hi) = (
+                    self.from[axis].min(self.to[axis]),
+                    self.from[axis].max(self.to[axis]),
+                );
+                let (other_lo, other_hi) = (
+                    other.from[axis].min(other.to[axis]),
+                    other.from[axis].max(other.to[axis]),
+                );
+                lo <= other_hi && other_lo <= hi
+            })
         }
-        assert_eq!(next_edges.len() % 2, 0); // check accounting
-        let next_edges: Vec<_> = next_edges.into_iter().collect(); // convert to vec
-
-        // now look for the cross-section of this row
-        let mut prev = edges.iter().peekable();
-        let mut next = next_edges.iter().peekable();
-        let mut start_x = None;
-        let mut state = (false, false);
-        loop {
-            let x = match (prev.peek(), next.peek()) {
-                (None, None) => break,
-                (None, Some(_n)) => {
-                    state.1 = !state.1;
-                    next.next().unwrap()
-                }
-                (Some(_p), None) => {
-                    state.0 = !state.0;
-                    prev.next().unwrap()
+    }
+
+    impl Polygon {
+        /// Follows horizontal or vertical moves from the origin.
+        /// Moves of length zero are skipped, all others are numbered from 0 in errors.
+        pub fn from_moves(moves: impl IntoIterator<Item = Pt>) -> Result<Self, PlanError> {
+            let mut segments = vec![];
+            let mut pos = [0, 0];
+            for (step, [dx, dy]) in moves.into_iter().enumerate() {
+                assert!(dx == 0 || dy == 0, "diagonal move");
+                if dx == 0 && dy == 0 {
+                    continue;
                 }
-                (Some(p), Some(n)) => {
-                    if p < n {
-                        state.0 = !state.0;
-                        prev.next().unwrap()
-                    } else {
-                        state.1 = !state.1;
-                        next.next().unwrap()
+                let from = pos;
+                pos = [pos[0] + dx, pos[1] + dy];
+                segments.push(Segment {
+                    from,
+                    to: pos,
+                    step,
+                });
+            }
+            if segments.is_empty() {
+                return Err(PlanError::Empty);
+            }
+            if pos != [0, 0] {
+                return Err(PlanError::NotClosed { end: pos });
+            }
+
+            let n = segments.len();
+            for (i, a) in segments.iter().enumerate() {
+                for (j, b) in segments.iter().enumerate().skip(i + 1) {
+                    let adjacent = j == i + 1 || (i == 0 && j == n - 1);
+                    // neighbours always share their corner, they only overlap when
+                    // the second one turns right back
+                    let bad = match adjacent {
+                        true => a.dir() == [-b.dir()[0], -b.dir()[1]],
+                        false => a.touches(b),
+                    };
+                    if bad {
+                        return Err(PlanError::SelfIntersecting {
+                            a: a.step,
+                            b: b.step,
+                        });
                     }
                 }
-            };
-            if !state.0 && !state.1 {
-                let delta_area = (*x - start_x.unwrap() + 1) as i64;
-                area += delta_area;
-                start_x = None;
-            } else if let None = start_x {
-                start_x = Some(x);
             }
+
+            let vertices = segments.iter().map(|segment| segment.from).collect();
+            Ok(Polygon { vertices })
         }
-        assert!(!state.0);
-        assert!(!state.1);
 
-        edges = next_edges;
-    }
+        fn edges(&self) -> impl Iterator<Item = (Pt, Pt)> + '_ {
+            let next = self.vertices.iter().cycle().skip(1);
+            self.vertices.iter().copied().zip(next.copied())
+        }
 
-    area
+        /// Twice the enclosed area, by the shoelace formula.
+        fn double_area(&self) -> i64 {
+            self.edges()
+                .map(|(a, b)| a[0] * b[1] - a[1] * b[0])
+                .sum::<i64>()
+                .abs()
+        }
+
+        /// Lattice points on the edges.
+        pub fn boundary(&self) -> i64 {
+            self.edges()
+                .map(|(a, b)| (b[0] - a[0]).abs() + (b[1] - a[1]).abs())
+                .sum()
+        }
+
+        /// Lattice points strictly inside, by Pick's theorem A = i + b/2 - 1.
+        pub fn interior(&self) -> i64 {
+            (self.double_area() - self.boundary()) / 2 + 1
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_part2() {
-        let input = "R 6 (#70c710)
+    const EXAMPLE: &str = "R 6 (#70c710)
         D 5 (#0dc571)
         L 2 (#5713f0)
         D 2 (#d2c081)
@@ -267,7 +331,127 @@ mod tests {
         U 3 (#a77fa3)
         L 2 (#015232)
         U 2 (#7a21e3)";
-        assert_eq!(part2(input), 952408144115);
-        assert_eq!(area_smart(&parse_part1(input)), 62);
+
+    #[test]
+    fn test_part2() {
+        assert_eq!(part2(EXAMPLE), Ok(952408144115));
+        assert_eq!(part1(EXAMPLE), Ok(62));
+    }
+
+    #[test]
+    fn test_colors() {
+        let plan = parse(EXAMPLE).unwrap();
+        assert_eq!(
+            plan[0],
+            Step {
+                dir: Dir::Right,
+                amt: 6,
+                color: 0x70c710
+            }
+        );
+        assert_eq!(
+            plan[0].decoded(),
+            Some(Step {
+                dir: Dir::Right,
+                amt: 461937,
+                color: 0x70c710
+            })
+        );
+        assert_eq!(plan[13].decoded().unwrap().dir, Dir::Up);
+    }
+
+    #[test]
+    fn test_bad_lines() {
+        let bad = |line: &str| DigError::Malformed {
+            step: 1,
+            line: line.to_string(),
+        };
+        for line in [
+            "R 6 (#70c71)",
+            "R 6 (#70c7100)",
+            "R 6 (#+0c710)",
+            "R 6 #70c710",
+            "X 6 (#70c710)",
+            "R (#70c710)",
+            "  ",
+        ] {
+            assert_eq!(part1(&format!("R 6 (#70c710)\n{line}")), Err(bad(line)));
+        }
+        assert_eq!(
+            part2("R 6 (#70c710)\nD 5 (#0dc574)\n"),
+            Err(DigError::BadDirection {
+                step: 1,
+                color: 0x0dc574
+            })
+        );
+        // only part 2 reads the direction from the colour
+        let square = "R 1 (#00000f)\nD 1 (#00000f)\nL 1 (#00000f)\nU 1 (#00000f)";
+        assert_eq!(part1(square), Ok(4));
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let plan = parse(EXAMPLE).unwrap();
+        let svg = render_svg(&plan, 70).unwrap();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.contains("width=\"70\" height=\"100\" viewBox=\"-0.5 -0.5 7 10\""));
+        assert!(svg.contains(
+            "<polygon points=\"0,0 6,0 6,5 4,5 4,7 6,7 6,9 1,9 1,7 0,7 0,5 2,5 2,2 0,2\""
+        ));
+        assert!(svg.contains("<line x1=\"0\" y1=\"0\" x2=\"6\" y2=\"0\" stroke=\"#70c710\""));
+        assert_eq!(svg.matches("<line ").count(), 14);
+
+        let svg = render_svg(&decode(&plan).unwrap(), 800).unwrap();
+        assert!(svg.contains("x2=\"461937\" y2=\"0\" stroke=\"#70c710\""));
+        assert!(svg.contains("stroke=\"#7a21e3\""));
+    }
+
+    #[test]
+    fn test_polygon() {
+        // 3x3 square of trench around a single cube
+        let square = Polygon::from_moves([[2, 0], [0, 2], [-2, 0], [0, -2]]).unwrap();
+        assert_eq!(square.vertices, vec![[0, 0], [2, 0], [2, 2], [0, 2]]);
+        assert_eq!((square.boundary(), square.interior()), (8, 1));
+        // going straight on after a corner is fine, so is a zero move
+        let split = Polygon::from_moves([[1, 0], [1, 0], [0, 0], [0, 2], [-2, 0], [0, -2]]);
+        assert_eq!(split.unwrap().interior(), 1);
+    }
+
+    #[test]
+    fn test_bad_plans() {
+        assert_eq!(part1(""), Err(DigError::Plan(PlanError::Empty)));
+        assert_eq!(
+            Polygon::from_moves([[0, 0], [0, 0]]).err(),
+            Some(PlanError::Empty)
+        );
+        assert_eq!(
+            Polygon::from_moves([[2, 0], [0, 2], [-1, 0]]).err(),
+            Some(PlanError::NotClosed { end: [1, 2] })
+        );
+        // figure eight crossing itself at [1, 0]
+        assert_eq!(
+            Polygon::from_moves([[2, 0], [0, 2], [-1, 0], [0, -3], [-1, 0], [0, 1]]).err(),
+            Some(PlanError::SelfIntersecting { a: 0, b: 3 })
+        );
+        // digging back over the trench just dug
+        assert_eq!(
+            Polygon::from_moves([[3, 0], [-1, 0], [0, 2], [-2, 0], [0, -2]]).err(),
+            Some(PlanError::SelfIntersecting { a: 0, b: 1 })
+        );
+        // two loops touching in a corner
+        let touching = [
+            [1, 0],
+            [0, 1],
+            [1, 0],
+            [0, 1],
+            [-1, 0],
+            [0, -1],
+            [-1, 0],
+            [0, -1],
+        ];
+        assert_eq!(
+            Polygon::from_moves(touching).err(),
+            Some(PlanError::SelfIntersecting { a: 1, b: 5 })
+        );
     }
 }